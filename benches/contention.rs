@@ -0,0 +1,78 @@
+//! Re-interning hot strings from many threads at once.
+//!
+//! Compare the global cache alone with the per-thread front cache. The
+//! `thread-cache` feature turns on `std`, which also swaps the bin locks for
+//! parking_lot, so enable `std` for the baseline too to only measure the front
+//! cache:
+//!
+//! ```sh
+//! cargo bench --bench contention --features std
+//! cargo bench --bench contention --features thread-cache
+//! ```
+
+use std::hint::black_box;
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use estr::estr;
+
+// Calls to `estr` made by each thread.
+const CALLS: usize = 2_000_000;
+
+// A parser's worth of hot identifiers and keywords.
+fn vocabulary() -> Vec<String> {
+    let keywords = [
+        "fn", "let", "mut", "if", "else", "match", "for", "while", "loop", "return", "struct",
+        "enum", "impl", "trait", "pub", "use", "mod", "self", "Self", "where",
+    ];
+    keywords
+        .iter()
+        .map(|k| k.to_string())
+        .chain((0..80).map(|i| format!("identifier_{i}")))
+        .collect()
+}
+
+fn run(threads: usize, words: &[String]) -> Duration {
+    let barrier = Barrier::new(threads);
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for t in 0..threads {
+            let barrier = &barrier;
+            scope.spawn(move || {
+                barrier.wait();
+                for i in 0..CALLS {
+                    black_box(estr(&words[(i * 7 + t) % words.len()]));
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn main() {
+    let words = vocabulary();
+    // Intern everything up front, so we only measure lookups of strings that
+    // are already present.
+    for w in &words {
+        estr(w);
+    }
+
+    let front = if cfg!(feature = "thread-cache") {
+        "with"
+    } else {
+        "without"
+    };
+    println!(
+        "re-interning {} hot strings, {front} the thread cache",
+        words.len()
+    );
+    for threads in [1, 2, 4, 8, 16] {
+        let elapsed = run(threads, &words);
+        let per_call = elapsed.as_nanos() as f64 / CALLS as f64;
+        let total = (threads * CALLS) as f64 / elapsed.as_secs_f64() / 1e6;
+        println!(
+            "{threads:>3} threads: {per_call:>6.1} ns/call per thread, {total:>8.1} M calls/s total"
+        );
+    }
+}
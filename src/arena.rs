@@ -0,0 +1,109 @@
+use alloc::alloc::{GlobalAlloc, Layout, alloc, dealloc};
+use core::{ptr, sync::atomic};
+
+/// The default arena, which takes string storage from the global heap.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HeapArena;
+
+unsafe impl GlobalAlloc for HeapArena {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { dealloc(ptr, layout) }
+    }
+}
+
+/// An arena that takes string storage from a fixed region of memory.
+///
+/// This is intended for targets without a heap large enough to hold the
+/// strings, or where they must live in a particular region. Memory is handed
+/// out from the start of the region and never reused, and once the region is
+/// full, allocation fails. Only string storage comes from the arena: the hash
+/// tables of the cache are still allocated on the heap.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{CacheConfig, Interner, StaticArena};
+///
+/// static mut POOL: [u8; 4096] = [0; 4096];
+/// static ARENA: StaticArena = StaticArena::new(unsafe { &mut *(&raw mut POOL) });
+///
+/// let interner = Interner::with_config(CacheConfig {
+///     bins: 1,
+///     initial_alloc: 1024,
+///     arena: &ARENA,
+///     ..CacheConfig::DEFAULT
+/// });
+/// let fox = interner.intern("the quick brown fox");
+/// assert_eq!(fox, "the quick brown fox");
+/// assert!(ARENA.used() >= 1024);
+/// ```
+pub struct StaticArena {
+    start: *mut u8,
+    capacity: usize,
+    used: atomic::AtomicUsize,
+}
+
+// The region is only reached through `alloc`, which hands out disjoint parts
+// of it using an atomic counter.
+unsafe impl Send for StaticArena {}
+unsafe impl Sync for StaticArena {}
+
+impl StaticArena {
+    /// Create an arena that allocates from the given region.
+    pub const fn new(region: &'static mut [u8]) -> StaticArena {
+        StaticArena {
+            start: region.as_mut_ptr(),
+            capacity: region.len(),
+            used: atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of bytes handed out so far, including any padding needed
+    /// for alignment.
+    pub fn used(&self) -> usize {
+        self.used.load(atomic::Ordering::Relaxed)
+    }
+
+    /// The size of the region in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+unsafe impl GlobalAlloc for StaticArena {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let start = self.start as usize;
+        let mut used = self.used.load(atomic::Ordering::Relaxed);
+        loop {
+            let Some(offset) = start
+                .checked_add(used)
+                .and_then(|addr| addr.checked_next_multiple_of(layout.align()))
+                .map(|addr| addr - start)
+            else {
+                return ptr::null_mut();
+            };
+            let end = match offset.checked_add(layout.size()) {
+                Some(end) if end <= self.capacity => end,
+                _ => return ptr::null_mut(),
+            };
+            match self.used.compare_exchange_weak(
+                used,
+                end,
+                atomic::Ordering::Relaxed,
+                atomic::Ordering::Relaxed,
+            ) {
+                // SAFETY: `offset` is within the region, checked above.
+                Ok(_) => return unsafe { self.start.add(offset) },
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        // Memory in the region is never reused.
+    }
+}
@@ -1,8 +1,15 @@
-use alloc::alloc::{Layout, alloc};
+use alloc::alloc::{GlobalAlloc, Layout};
 
-// The world's dumbest allocator. Just keep bumping a pointer until we run out
-// of memory, in which case we abort. StringCache is responsible for creating
-// a new allocator when that's about to happen.
+// The world's dumbest allocator. Just keep bumping a pointer until the chunk
+// is full. StringCache checks that each string fits before allocating it, and
+// either creates a new allocator or returns an error when it does not, so
+// running off the end of a chunk is a bug and aborts.
+//
+// Each allocator takes its memory from an arena, which is the heap unless the
+// cache was configured otherwise. The memory is only returned when the
+// allocator is dropped. That never happens for the global cache, which is
+// what lets it hand out 'static strings, but does for the caches owned by an
+// `Interner`.
 //
 // This is now bumping downward rather than up, which simplifies the allocate()
 // method and gives a small (5-7%) performance improvement in multithreaded
@@ -10,6 +17,7 @@ use alloc::alloc::{Layout, alloc};
 //
 // See https://fitzgeraldnick.com/2019/11/01/always-bump-downwards.html
 pub(crate) struct LeakyBumpAlloc {
+    arena: &'static (dyn GlobalAlloc + Sync),
     layout: Layout,
     start: *mut u8,
     end: *mut u8,
@@ -17,24 +25,32 @@ pub(crate) struct LeakyBumpAlloc {
 }
 
 impl LeakyBumpAlloc {
-    pub fn new(capacity: usize, alignment: usize) -> LeakyBumpAlloc {
-        let layout = Layout::from_size_align(capacity, alignment).unwrap();
-        // SAFETY: TODO
-        let start = unsafe { alloc(layout) };
+    // Take a new chunk from the arena, or `None` if it is out of memory.
+    pub fn new(
+        capacity: usize,
+        alignment: usize,
+        arena: &'static (dyn GlobalAlloc + Sync),
+    ) -> Option<LeakyBumpAlloc> {
+        let layout = Layout::from_size_align(capacity, alignment).ok()?;
+        // SAFETY: the layout is never zero-sized, as `StringCache` always asks
+        // for room for at least one entry.
+        let start = unsafe { arena.alloc(layout) };
         if start.is_null() {
-            panic!("oom");
+            return None;
         }
         let end = unsafe { start.add(layout.size()) };
         let ptr = end;
-        LeakyBumpAlloc {
+        Some(LeakyBumpAlloc {
+            arena,
             layout,
             start,
             end,
             ptr,
-        }
+        })
     }
 
-    // Allocates a new chunk. Aborts if out of memory.
+    // Allocates a new chunk. Aborts if the chunk is full, which `StringCache`
+    // makes sure never happens.
     pub unsafe fn allocate(&mut self, num_bytes: usize) -> *mut u8 {
         // Our new ptr will be offset down the heap by num_bytes bytes.
         let ptr = self.ptr as usize;
@@ -61,4 +77,18 @@ impl LeakyBumpAlloc {
     pub fn capacity(&self) -> usize {
         self.layout.size()
     }
+
+    // The range of memory handed out so far, from the most recent allocation
+    // up to the end of the chunk.
+    pub fn used(&self) -> (*const u8, *const u8) {
+        (self.ptr, self.end)
+    }
+}
+
+impl Drop for LeakyBumpAlloc {
+    fn drop(&mut self) {
+        // SAFETY: `start` was allocated in `new` from this arena with this
+        // same layout.
+        unsafe { self.arena.dealloc(self.start, self.layout) };
+    }
 }
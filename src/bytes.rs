@@ -0,0 +1,224 @@
+use core::{cmp, fmt, hash, ops, ptr, slice, str};
+
+use crate::config::config;
+use crate::error::InternError;
+use crate::stringcache::{Bin, NUM_BINS, StringCache, StringCacheEntry};
+use crate::{Digest, Estr, USAGE, digest_bytes, whichbin};
+
+/// A handle representing a byte string in the global cache.
+///
+/// This is the counterpart of [`Estr`] for data that need not be UTF-8, such
+/// as keys in a binary protocol or file names. Byte strings are interned in
+/// bins of their own, laid out just like those of strings and sharing the
+/// same configuration and budget, so an `EBytes` is also a single pointer with
+/// O(1) equality and a precomputed hash.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{EBytes, EBytesMap, estr};
+///
+/// let key = EBytes::from(b"\xff\x00key");
+/// assert_eq!(key, EBytes::from(b"\xff\x00key"));
+/// assert_eq!(key.len(), 5);
+/// assert_eq!(key.to_estr(), None);
+///
+/// let fox = EBytes::from(b"fox");
+/// assert_eq!(fox.to_estr(), Some(estr("fox")));
+///
+/// let mut lengths = EBytesMap::default();
+/// lengths.insert(key, key.len());
+/// assert_eq!(lengths[&EBytes::from(b"\xff\x00key")], 5);
+/// ```
+#[derive(Copy, Clone)]
+#[repr(transparent)]
+pub struct EBytes {
+    char_ptr: ptr::NonNull<u8>,
+}
+
+// Byte strings are kept apart from strings, so that an `Estr` can never be
+// made from bytes that are not UTF-8.
+static BYTES_CACHE: [Bin; NUM_BINS] = [const { Bin::new() }; NUM_BINS];
+
+fn bytes_bin(hash: u64) -> &'static Bin {
+    &BYTES_CACHE[whichbin(hash, config().bins)]
+}
+
+impl EBytes {
+    /// Create a new `EBytes` from the given bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache runs out of memory or goes over its budget. Use
+    /// [`EBytes::try_from_bytes`] to handle that instead.
+    pub fn new(bytes: &[u8]) -> EBytes {
+        EBytes::try_from_bytes(bytes).expect("failed to intern byte string")
+    }
+
+    /// Create a new `EBytes` from the given bytes, or return an error if the
+    /// cache runs out of memory or would go over its budget.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<EBytes, InternError> {
+        let Digest { hash } = digest_bytes(bytes);
+        let ptr =
+            bytes_bin(hash).with_cache(StringCache::new, |sc| sc.insert(bytes, hash, &USAGE))?;
+        Ok(EBytes {
+            // SAFETY: sc.insert does not give back a null pointer
+            char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+        })
+    }
+
+    /// Get the `EBytes` for the given bytes, but only if they have already
+    /// been interned.
+    pub fn from_existing(bytes: &[u8]) -> Option<EBytes> {
+        let Digest { hash } = digest_bytes(bytes);
+        bytes_bin(hash).get_existing(bytes, hash).map(|ptr| EBytes {
+            char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+        })
+    }
+
+    /// Get the interned bytes as a slice.
+    #[inline]
+    pub fn as_bytes(&self) -> &'static [u8] {
+        // This is safe for the same reasons as `Estr::as_str`.
+        unsafe { slice::from_raw_parts(self.char_ptr.as_ptr(), self.len()) }
+    }
+
+    /// Get the bytes as a `str`, if they are valid UTF-8.
+    #[inline]
+    pub fn as_str(&self) -> Option<&'static str> {
+        str::from_utf8(self.as_bytes()).ok()
+    }
+
+    /// Get an `Estr` with the same contents, if the bytes are valid UTF-8.
+    ///
+    /// The hash is reused, so this only checks the bytes and looks them up in
+    /// the string cache, interning them there if need be.
+    pub fn to_estr(&self) -> Option<Estr> {
+        self.as_str()
+            .map(|string| Estr::from_with_digest(string, self.digest()))
+    }
+
+    /// Get a raw pointer to the `StringCacheEntry`.
+    #[inline]
+    fn as_string_cache_entry(&self) -> &StringCacheEntry {
+        // The allocator guarantees that the alignment is correct and that
+        // this pointer is non-null
+        unsafe { &*(self.char_ptr.as_ptr().cast::<StringCacheEntry>().sub(1)) }
+    }
+
+    /// Get the length (in bytes) of this byte string.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.as_string_cache_entry().len
+    }
+
+    /// Returns `true` if this is the empty byte string.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the precomputed hash for this byte string.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        Digest {
+            hash: self.as_string_cache_entry().hash,
+        }
+    }
+}
+
+// The bytes are immutable and never freed.
+unsafe impl Send for EBytes {}
+unsafe impl Sync for EBytes {}
+
+impl Default for EBytes {
+    fn default() -> Self {
+        EBytes::new(b"")
+    }
+}
+
+impl PartialEq for EBytes {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        // Every `EBytes` comes from the cache, which holds each byte string
+        // once.
+        self.char_ptr == other.char_ptr
+    }
+}
+
+impl Eq for EBytes {}
+
+impl PartialOrd for EBytes {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EBytes {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.digest()
+            .cmp(&other.digest())
+            .then_with(|| self.as_bytes().cmp(other.as_bytes()))
+    }
+}
+
+impl hash::Hash for EBytes {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.digest().hash.hash(state);
+    }
+}
+
+impl PartialEq<[u8]> for EBytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl PartialEq<&[u8]> for EBytes {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.as_bytes() == *other
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for EBytes {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl From<&[u8]> for EBytes {
+    fn from(bytes: &[u8]) -> EBytes {
+        EBytes::new(bytes)
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for EBytes {
+    fn from(bytes: &[u8; N]) -> EBytes {
+        EBytes::new(bytes)
+    }
+}
+
+impl From<Estr> for EBytes {
+    fn from(e: Estr) -> EBytes {
+        EBytes::new(e.as_str().as_bytes())
+    }
+}
+
+impl AsRef<[u8]> for EBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl ops::Deref for EBytes {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Debug for EBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b\"{}\"", self.as_bytes().escape_ascii())
+    }
+}
@@ -1,4 +1,6 @@
 crossfig::alias! {
     pub std: { #[cfg(feature = "std")] },
-    pub spin: { #[cfg(feature = "spin")] }
+    pub spin: { #[cfg(feature = "spin")] },
+    pub serde: { #[cfg(feature = "serde")] },
+    pub thread_cache: { #[cfg(feature = "thread-cache")] }
 }
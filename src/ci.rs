@@ -0,0 +1,190 @@
+use alloc::borrow::Cow;
+use core::{cmp, fmt, hash, ops};
+
+use crate::{Digest, Estr};
+
+/// An `Estr` that compares and hashes without regard to case.
+///
+/// Names such as HTTP headers or SQL identifiers are the same however they
+/// are capitalized. An `EstrCi` interns the lowercase form of the string for
+/// equality, ordering and hashing, and separately the spelling it was created
+/// from, for display. Both are interned, so comparisons stay as cheap as for
+/// an `Estr`.
+///
+/// Strings are lowercased with [`str::to_lowercase`]. This is not full Unicode
+/// case folding, so for example `"straße"` and `"STRASSE"` are different.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{EstrCi, EstrCiMap, estr};
+///
+/// let a = EstrCi::new("Content-Type");
+/// let b = EstrCi::new("content-type");
+/// assert_eq!(a, b);
+/// assert_eq!(a.as_str(), "Content-Type");
+/// assert_eq!(b.folded(), estr("content-type"));
+///
+/// let mut headers = EstrCiMap::default();
+/// headers.insert(a, "text/plain");
+/// assert_eq!(headers.get(&EstrCi::new("CONTENT-TYPE")), Some(&"text/plain"));
+/// ```
+#[derive(Copy, Clone)]
+pub struct EstrCi {
+    folded: Estr,
+    original: Estr,
+}
+
+// Lowercase a string, without allocating if it is lowercase already.
+fn fold(string: &str) -> Cow<'_, str> {
+    if string.is_ascii() {
+        if string.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(string.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(string)
+        }
+    } else {
+        let folded = string.to_lowercase();
+        if folded == string {
+            Cow::Borrowed(string)
+        } else {
+            Cow::Owned(folded)
+        }
+    }
+}
+
+impl EstrCi {
+    /// Create a new `EstrCi` from the given `str`, interning both it and its
+    /// lowercase form.
+    pub fn new(string: &str) -> EstrCi {
+        EstrCi::from(Estr::from(string))
+    }
+
+    /// Get the spelling this `EstrCi` was created from.
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        self.original.as_str()
+    }
+
+    /// Get the spelling this `EstrCi` was created from, as an `Estr`.
+    #[inline]
+    pub fn original(&self) -> Estr {
+        self.original
+    }
+
+    /// Get the lowercase form of the string, which decides its identity.
+    #[inline]
+    pub fn folded(&self) -> Estr {
+        self.folded
+    }
+
+    /// Get the precomputed hash of the lowercase form.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        self.folded.digest()
+    }
+}
+
+impl Default for EstrCi {
+    fn default() -> Self {
+        EstrCi::from(Estr::default())
+    }
+}
+
+impl PartialEq for EstrCi {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.folded == other.folded
+    }
+}
+
+impl Eq for EstrCi {}
+
+impl PartialOrd for EstrCi {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EstrCi {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.folded.cmp(&other.folded)
+    }
+}
+
+// Hash the lowercase form like an `Estr`, so that an `EstrCi` can key a map
+// with the identity hasher.
+impl hash::Hash for EstrCi {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.folded.hash(state);
+    }
+}
+
+/// Compares without regard to case.
+///
+/// # Examples
+///
+/// ```
+/// use estr::EstrCi;
+///
+/// assert_eq!(EstrCi::new("SELECT"), "select");
+/// assert_ne!(EstrCi::new("SELECT"), "selected");
+/// ```
+impl PartialEq<str> for EstrCi {
+    fn eq(&self, other: &str) -> bool {
+        self.folded.as_str() == fold(other)
+    }
+}
+
+impl PartialEq<&str> for EstrCi {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl From<Estr> for EstrCi {
+    fn from(original: Estr) -> EstrCi {
+        let folded = match fold(original.as_str()) {
+            Cow::Borrowed(_) => original,
+            Cow::Owned(folded) => Estr::from(&folded),
+        };
+        EstrCi { folded, original }
+    }
+}
+
+impl From<&str> for EstrCi {
+    fn from(s: &str) -> EstrCi {
+        EstrCi::new(s)
+    }
+}
+
+impl From<EstrCi> for Estr {
+    fn from(e: EstrCi) -> Estr {
+        e.original
+    }
+}
+
+impl AsRef<str> for EstrCi {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl ops::Deref for EstrCi {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for EstrCi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.original, f)
+    }
+}
+
+impl fmt::Debug for EstrCi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.original, f)
+    }
+}
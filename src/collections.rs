@@ -1,9 +1,10 @@
+use alloc::collections::{BTreeMap, BTreeSet};
 use core::hash::{BuildHasherDefault, Hasher};
 
 use byteorder::{ByteOrder, NativeEndian};
 use hashbrown::{HashMap, HashSet};
 
-use super::Estr;
+use super::{EBytes, Estr, EstrCi, LexEstr, cfg};
 
 /// A standard `HashMap` using `Estr` as the key type with a custom `Hasher`
 /// that just uses the precomputed hash for speed instead of calculating it.
@@ -13,6 +14,42 @@ pub type EstrMap<V> = HashMap<Estr, V, BuildHasherDefault<IdentityHasher>>;
 /// that just uses the precomputed hash for speed instead of calculating it.
 pub type EstrSet = HashSet<Estr, BuildHasherDefault<IdentityHasher>>;
 
+/// A standard `HashMap` using `EstrCi` as the key type, so keys that differ
+/// only in case are the same key, with the same fast hasher as [`EstrMap`].
+pub type EstrCiMap<V> = HashMap<EstrCi, V, BuildHasherDefault<IdentityHasher>>;
+
+/// A standard `HashSet` of `EstrCi`, so strings that differ only in case are
+/// the same member, with the same fast hasher as [`EstrSet`].
+pub type EstrCiSet = HashSet<EstrCi, BuildHasherDefault<IdentityHasher>>;
+
+/// A standard `HashMap` using `EBytes` as the key type, with the same fast
+/// hasher as [`EstrMap`].
+pub type EBytesMap<V> = HashMap<EBytes, V, BuildHasherDefault<IdentityHasher>>;
+
+/// A standard `HashSet` of `EBytes`, with the same fast hasher as
+/// [`EstrSet`].
+pub type EBytesSet = HashSet<EBytes, BuildHasherDefault<IdentityHasher>>;
+
+cfg::std! {
+    use super::EPath;
+
+    /// A standard `HashMap` using `EPath` as the key type, with the same fast
+    /// hasher as [`EstrMap`].
+    pub type EPathMap<V> = HashMap<EPath, V, BuildHasherDefault<IdentityHasher>>;
+
+    /// A standard `HashSet` of `EPath`, with the same fast hasher as
+    /// [`EstrSet`].
+    pub type EPathSet = HashSet<EPath, BuildHasherDefault<IdentityHasher>>;
+}
+
+/// A `BTreeMap` keyed by `Estr` in lexicographic order, for output that will be
+/// read by a person.
+pub type LexEstrMap<V> = BTreeMap<LexEstr, V>;
+
+/// A `BTreeSet` of `Estr` in lexicographic order, for output that will be read
+/// by a person.
+pub type LexEstrSet = BTreeSet<LexEstr>;
+
 /// The worst hasher in the world -- the identity hasher.
 #[doc(hidden)]
 #[derive(Default)]
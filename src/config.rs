@@ -0,0 +1,272 @@
+use alloc::alloc::GlobalAlloc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::{fmt, hint};
+
+use crate::arena::HeapArena;
+use crate::stringcache::{INITIAL_ALLOC, INITIAL_CAPACITY, NUM_BINS};
+
+/// The geometry of a string cache.
+///
+/// The global cache uses [`CacheConfig::DEFAULT`] unless [`configure`] is
+/// called before it is first used. An [`Interner`](crate::Interner) can be
+/// given its own configuration with
+/// [`Interner::with_config`](crate::Interner::with_config).
+///
+/// The cache is split into a number of bins, each with its own table and
+/// allocator, and nothing is allocated for a bin until a string lands in it.
+/// The capacities are totals, divided evenly among the bins. Both are only
+/// starting points, and the cache grows past them as needed, up to the
+/// budget set by `max_bytes` and `max_entries`.
+///
+/// # Examples
+///
+/// A tiny cache still holds any number of strings, it just starts small.
+///
+/// ```
+/// use estr::{CacheConfig, Interner};
+///
+/// let interner = Interner::with_config(CacheConfig {
+///     bins: 1,
+///     initial_capacity: 2,
+///     initial_alloc: 16,
+///     ..CacheConfig::DEFAULT
+/// });
+/// let strings: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+/// let handles: Vec<_> = strings.iter().map(|s| interner.intern(s)).collect();
+/// assert!(strings.iter().zip(handles).all(|(s, e)| e == s.as_str()));
+/// assert_eq!(interner.stats().bins.len(), 1);
+/// ```
+///
+/// A huge cache only pays for the bins it uses.
+///
+/// ```
+/// use estr::{CacheConfig, Interner};
+///
+/// let interner = Interner::with_config(CacheConfig {
+///     initial_capacity: 1 << 26,
+///     initial_alloc: 1 << 30,
+///     ..CacheConfig::DEFAULT
+/// });
+/// interner.intern("the quick brown fox");
+/// let stats = interner.stats();
+/// assert_eq!(stats.entries, 1);
+/// assert_eq!(stats.table_capacity, (1 << 26) / 64);
+/// ```
+///
+/// A budget keeps untrusted input from growing the global cache without
+/// bound. Strings that do not fit can be kept as owned strings instead.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use estr::{CacheConfig, Estr, InternError, configure};
+///
+/// configure(CacheConfig {
+///     max_entries: 2,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// fn intern(s: &str) -> Cow<'static, str> {
+///     match Estr::try_from_str(s) {
+///         Ok(e) => Cow::Borrowed(e.as_str()),
+///         Err(InternError::BudgetExceeded) => Cow::Owned(s.to_owned()),
+///         Err(err) => panic!("{err}"),
+///     }
+/// }
+///
+/// assert!(matches!(intern("the"), Cow::Borrowed("the")));
+/// assert!(matches!(intern("quick"), Cow::Borrowed("quick")));
+/// assert!(matches!(intern("brown"), Cow::Owned(_)));
+/// // Strings already in the cache are still found.
+/// assert!(matches!(intern("the"), Cow::Borrowed("the")));
+/// ```
+///
+/// An [`Interner`](crate::Interner) counts against its own budget.
+///
+/// ```
+/// use estr::{CacheConfig, InternError, Interner};
+///
+/// let interner = Interner::with_config(CacheConfig {
+///     max_bytes: 256,
+///     ..CacheConfig::DEFAULT
+/// });
+/// let strings: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+/// let interned = strings.iter().take_while(|s| interner.try_intern(s).is_ok());
+/// assert_eq!(interned.count(), 256 / 24);
+/// assert!(interner.stats().allocated_bytes <= 256);
+/// assert_eq!(interner.try_intern("fox"), Err(InternError::BudgetExceeded));
+/// ```
+#[derive(Copy, Clone)]
+pub struct CacheConfig {
+    /// Number of bins (shards), each with its own lock. This is rounded up to
+    /// a power of two, and may be at most 64.
+    pub bins: usize,
+    /// Number of slots in the hash tables, across all bins. The cache grows
+    /// when it is half full.
+    pub initial_capacity: usize,
+    /// Size in bytes of the first string allocator, across all bins. Each
+    /// string takes its length plus a header of two words, a null
+    /// terminator, and padding up to a word.
+    pub initial_alloc: usize,
+    /// Where the memory for strings comes from. Each bin takes chunks from
+    /// the arena as it fills up, starting at `initial_alloc / bins` bytes and
+    /// doubling each time. Chunks are only returned when an
+    /// [`Interner`](crate::Interner) is dropped, never by the global cache.
+    pub arena: &'static (dyn GlobalAlloc + Sync),
+    /// The most memory that strings may take up, in bytes, across all bins.
+    /// This counts each string's header, null terminator and padding, as
+    /// reported by [`CacheStats::allocated_bytes`](crate::CacheStats), but not
+    /// the hash tables. Interning a new string that would exceed this fails
+    /// with [`InternError::BudgetExceeded`](crate::InternError).
+    pub max_bytes: usize,
+    /// The most strings the cache may hold, across all bins. Interning a new
+    /// string beyond this fails with
+    /// [`InternError::BudgetExceeded`](crate::InternError).
+    pub max_entries: usize,
+}
+
+impl CacheConfig {
+    /// The configuration of the global cache if [`configure`] is not called:
+    /// 64 bins, with 2^20 slots and 4 MiB of string storage in total to
+    /// start with, and no limit on how far it may grow.
+    pub const DEFAULT: CacheConfig = CacheConfig {
+        bins: NUM_BINS,
+        initial_capacity: INITIAL_CAPACITY,
+        initial_alloc: INITIAL_ALLOC,
+        arena: &HeapArena,
+        max_bytes: usize::MAX,
+        max_entries: usize::MAX,
+    };
+
+    // Round the number of bins to something we can use.
+    pub(crate) fn normalized(mut self) -> CacheConfig {
+        self.bins = self.bins.clamp(1, NUM_BINS).next_power_of_two();
+        self
+    }
+
+    // The starting table size and allocator size for each bin.
+    pub(crate) fn per_bin(&self) -> (usize, usize) {
+        (
+            self.initial_capacity / self.bins,
+            self.initial_alloc / self.bins,
+        )
+    }
+}
+
+impl fmt::Debug for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheConfig")
+            .field("bins", &self.bins)
+            .field("initial_capacity", &self.initial_capacity)
+            .field("initial_alloc", &self.initial_alloc)
+            .field("max_bytes", &self.max_bytes)
+            .field("max_entries", &self.max_entries)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig::DEFAULT
+    }
+}
+
+/// Set the geometry of the global string cache.
+///
+/// This must be called before any string is interned, or any other use of
+/// the global cache. After that the configuration is fixed, and this returns
+/// the rejected configuration as an error.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{CacheConfig, cache_stats, configure, estr};
+///
+/// configure(CacheConfig {
+///     bins: 4,
+///     initial_capacity: 64,
+///     initial_alloc: 1024,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// estr("the quick brown fox");
+/// assert_eq!(cache_stats().table_capacity, 16);
+/// assert!(configure(CacheConfig::DEFAULT).is_err());
+/// ```
+pub fn configure(config: CacheConfig) -> Result<(), CacheConfig> {
+    GLOBAL_CONFIG.set(config.normalized())
+}
+
+// The configuration of the global cache.
+pub(crate) fn config() -> &'static CacheConfig {
+    GLOBAL_CONFIG.get()
+}
+
+static GLOBAL_CONFIG: ConfigCell = ConfigCell::new();
+
+// A write-once cell for the global configuration. It is frozen, with the
+// default configuration if need be, the first time it is read.
+struct ConfigCell {
+    state: AtomicU8,
+    config: UnsafeCell<CacheConfig>,
+}
+
+const UNSET: u8 = 0;
+const WRITING: u8 = 1;
+const FROZEN: u8 = 2;
+
+// The config is only written while `state` is `WRITING`, which only one
+// thread can observe, and only read once `state` is `FROZEN`.
+unsafe impl Sync for ConfigCell {}
+
+impl ConfigCell {
+    const fn new() -> ConfigCell {
+        ConfigCell {
+            state: AtomicU8::new(UNSET),
+            config: UnsafeCell::new(CacheConfig::DEFAULT),
+        }
+    }
+
+    fn set(&self, config: CacheConfig) -> Result<(), CacheConfig> {
+        if self
+            .state
+            .compare_exchange(UNSET, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(config);
+        }
+        // SAFETY: we hold the `WRITING` state, so there are no other readers
+        // or writers.
+        unsafe { *self.config.get() = config };
+        self.state.store(FROZEN, Ordering::Release);
+        Ok(())
+    }
+
+    #[inline]
+    fn get(&self) -> &CacheConfig {
+        if self.state.load(Ordering::Acquire) != FROZEN {
+            self.freeze();
+        }
+        // SAFETY: the config is frozen, so it will never be written again.
+        unsafe { &*self.config.get() }
+    }
+
+    // Freeze the default configuration, or wait for a concurrent `set` to
+    // finish.
+    #[cold]
+    fn freeze(&self) {
+        loop {
+            match self.state.compare_exchange_weak(
+                UNSET,
+                FROZEN,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) | Err(FROZEN) => return,
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use core::{error, fmt};
+
+/// The error returned when a string could not be interned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InternError {
+    /// The arena could not provide memory for the string, or for the cache
+    /// to grow.
+    OutOfMemory,
+    /// Interning the string would take the cache over the byte or entry
+    /// budget in its [`CacheConfig`](crate::CacheConfig).
+    BudgetExceeded,
+}
+
+impl fmt::Display for InternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InternError::OutOfMemory => f.write_str("out of memory"),
+            InternError::BudgetExceeded => f.write_str("string cache budget exceeded"),
+        }
+    }
+}
+
+impl error::Error for InternError {}
+
+/// The error returned when a snapshot could not be loaded by
+/// [`snapshot::load`](crate::snapshot::load).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// The data does not start with a snapshot header.
+    NotASnapshot,
+    /// The snapshot was written by a different version of the format, or on
+    /// a target with a different word size, byte order or hash function.
+    Incompatible,
+    /// The snapshot passed to [`snapshot::attach`](crate::snapshot::attach)
+    /// is not aligned for its entries.
+    Misaligned,
+    /// An entry in the snapshot is cut short or malformed, or its hash does
+    /// not match its string.
+    Corrupt {
+        /// The offset of the entry in the snapshot, in bytes.
+        offset: usize,
+    },
+    /// A string could not be added to the cache. Some of the other strings
+    /// in the snapshot may have been loaded already.
+    Intern(InternError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => f.write_str("not a string cache snapshot"),
+            SnapshotError::Incompatible => f.write_str("incompatible string cache snapshot"),
+            SnapshotError::Misaligned => f.write_str("misaligned string cache snapshot"),
+            SnapshotError::Corrupt { offset } => {
+                write!(f, "corrupt string cache snapshot entry at offset {offset}")
+            }
+            SnapshotError::Intern(err) => write!(f, "failed to load snapshot: {err}"),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SnapshotError::Intern(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<InternError> for SnapshotError {
+    fn from(err: InternError) -> SnapshotError {
+        SnapshotError::Intern(err)
+    }
+}
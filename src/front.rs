@@ -0,0 +1,60 @@
+// A small per-thread cache of recently interned strings, in front of the
+// global cache.
+//
+// Interning a string that is already in the cache still locks its bin. With
+// the `thread-cache` feature, each thread also remembers the last string it
+// interned for each of `SLOTS` recent hashes, so that interning hot strings
+// again only hashes and compares them. Entries are never invalidated, since
+// strings in the global cache live forever. Without the feature this is
+// compiled out entirely.
+
+use crate::{Digest, Estr, cfg};
+
+crossfig::switch! {
+    cfg::thread_cache => {
+        use core::cell::Cell;
+
+        // Number of slots in each thread's cache. Must be a power of two.
+        const SLOTS: usize = 256;
+
+        std::thread_local! {
+            static FRONT: [Cell<Option<Estr>>; SLOTS] = const { [const { Cell::new(None) }; SLOTS] };
+        }
+
+        // The slot for a hash. The top bits choose the bin, so use the bottom
+        // ones to spread strings from the same bin.
+        #[inline]
+        fn slot(hash: u64) -> usize {
+            hash as usize & (SLOTS - 1)
+        }
+
+        // Find the string in this thread's cache.
+        #[inline]
+        pub(crate) fn get(string: &str, digest: Digest) -> Option<Estr> {
+            FRONT
+                .try_with(|front| {
+                    let e = front[slot(digest.hash)].get()?;
+                    (e.digest() == digest && e.as_str() == string).then_some(e)
+                })
+                .ok()
+                .flatten()
+        }
+
+        // Remember a string that was just interned by this thread.
+        #[inline]
+        pub(crate) fn put(e: Estr) {
+            // During thread teardown the cache may already be gone, in which
+            // case there is nothing to remember the string in.
+            let _ = FRONT.try_with(|front| front[slot(e.digest().hash)].set(Some(e)));
+        }
+    }
+    _ => {
+        #[inline(always)]
+        pub(crate) fn get(_string: &str, _digest: Digest) -> Option<Estr> {
+            None
+        }
+
+        #[inline(always)]
+        pub(crate) fn put(_e: Estr) {}
+    }
+}
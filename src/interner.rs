@@ -0,0 +1,278 @@
+use alloc::{boxed, slice, str, vec};
+use core::{cmp, fmt, hash, marker, ops, ptr};
+
+use crate::config::CacheConfig;
+use crate::error::InternError;
+use crate::stringcache::{Bin, CacheStats, StringCache, StringCacheEntry, Usage};
+use crate::{Digest, digest, whichbin};
+
+/// A private string cache that frees its memory when dropped.
+///
+/// The global cache used by [`Estr`](crate::Estr) lives for the whole program
+/// and never gives memory back. An `Interner` has the same layout, but hands
+/// out [`LocalEstr`] handles that borrow from it, so all of its strings are
+/// freed together when it goes out of scope. This suits symbol tables that
+/// only matter for a single request or compilation unit.
+///
+/// Unlike the global cache, a new interner has a single bin, since it is
+/// usually owned by one thread and every bin costs a few hundred bytes up
+/// front. An interner shared between many threads can be split into more bins
+/// with [`Interner::with_config`].
+///
+/// # Examples
+///
+/// ```
+/// use estr::Interner;
+///
+/// let interner = Interner::new();
+/// let a = interner.intern("the quick brown fox");
+/// let b = interner.intern("the quick brown fox");
+/// assert_eq!(a, b);
+/// assert_eq!(interner.get("the lazy dog"), None);
+/// assert_eq!(interner.stats().bins.len(), 1);
+/// ```
+pub struct Interner {
+    bins: boxed::Box<[Bin]>,
+    config: CacheConfig,
+    usage: Usage,
+}
+
+// Defaults for a new `Interner`, much smaller than the global cache since
+// we expect to have many of these.
+const DEFAULT_BINS: usize = 1;
+const DEFAULT_CAPACITY: usize = 1 << 10;
+const DEFAULT_ALLOC: usize = 16 << 10;
+
+impl Interner {
+    /// Create an empty interner with a single bin.
+    ///
+    /// No memory is allocated for the bin until a string is interned in it.
+    pub fn new() -> Interner {
+        Interner::with_capacity(DEFAULT_CAPACITY, DEFAULT_ALLOC)
+    }
+
+    /// Create an empty interner sized for roughly `strings` strings totalling
+    /// `bytes` bytes, in a single bin. The interner will still grow past this
+    /// if needed.
+    pub fn with_capacity(strings: usize, bytes: usize) -> Interner {
+        Interner::with_config(CacheConfig {
+            bins: DEFAULT_BINS,
+            initial_capacity: strings,
+            initial_alloc: bytes,
+            ..CacheConfig::DEFAULT
+        })
+    }
+
+    /// Create an empty interner with the given geometry.
+    pub fn with_config(config: CacheConfig) -> Interner {
+        let config = config.normalized();
+        Interner {
+            bins: (0..config.bins).map(|_| Bin::new()).collect(),
+            config,
+            usage: Usage::new(),
+        }
+    }
+
+    /// Intern the given `str`, returning a handle that lives as long as the
+    /// interner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interner runs out of memory. Use
+    /// [`Interner::try_intern`] to handle that instead.
+    pub fn intern(&self, string: &str) -> LocalEstr<'_> {
+        self.try_intern(string).expect("failed to intern string")
+    }
+
+    /// Intern the given `str`, or return an error if the interner runs out of
+    /// memory or goes over its budget.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::alloc::{GlobalAlloc, Layout};
+    /// use estr::{CacheConfig, InternError, Interner};
+    ///
+    /// struct Exhausted;
+    ///
+    /// unsafe impl GlobalAlloc for Exhausted {
+    ///     unsafe fn alloc(&self, _: Layout) -> *mut u8 {
+    ///         std::ptr::null_mut()
+    ///     }
+    ///     unsafe fn dealloc(&self, _: *mut u8, _: Layout) {}
+    /// }
+    ///
+    /// let interner = Interner::with_config(CacheConfig {
+    ///     arena: &Exhausted,
+    ///     ..CacheConfig::DEFAULT
+    /// });
+    /// assert_eq!(interner.try_intern("fox"), Err(InternError::OutOfMemory));
+    /// assert_eq!(interner.get("fox"), None);
+    /// ```
+    pub fn try_intern(&self, string: &str) -> Result<LocalEstr<'_>, InternError> {
+        let Digest { hash } = digest(string);
+        let ptr = self.bins[whichbin(hash, self.bins.len())].with_cache(
+            || StringCache::with_config(&self.config),
+            |sc| sc.insert(string.as_bytes(), hash, &self.usage),
+        )?;
+        Ok(LocalEstr {
+            // SAFETY: sc.insert does not give back a null pointer
+            char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+            _interner: marker::PhantomData,
+        })
+    }
+
+    /// Get the handle for the given `str`, but only if it has already been
+    /// interned.
+    pub fn get(&self, string: &str) -> Option<LocalEstr<'_>> {
+        let Digest { hash } = digest(string);
+        self.bins[whichbin(hash, self.bins.len())]
+            .get_existing(string.as_bytes(), hash)
+            .map(|ptr| LocalEstr {
+                char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+                _interner: marker::PhantomData,
+            })
+    }
+
+    /// Collect statistics about the memory used by this interner.
+    pub fn stats(&self) -> CacheStats {
+        let bins = self
+            .bins
+            .iter()
+            .map(|bin| {
+                bin.with_existing_cache(StringCache::stats)
+                    .unwrap_or_default()
+            })
+            .collect::<vec::Vec<_>>();
+        CacheStats::from_bins(bins)
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+/// A handle representing a string in an [`Interner`].
+///
+/// This behaves like an [`Estr`](crate::Estr), except that it borrows from the
+/// interner that created it.
+#[derive(Copy, Clone)]
+#[repr(transparent)]
+pub struct LocalEstr<'i> {
+    char_ptr: ptr::NonNull<u8>,
+    _interner: marker::PhantomData<&'i Interner>,
+}
+
+impl<'i> LocalEstr<'i> {
+    /// Get the interned string as a `str`.
+    pub fn as_str(&self) -> &'i str {
+        // This is safe for the same reasons as `Estr::as_str`, and because the
+        // interner keeps the memory alive for at least 'i.
+        unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(self.char_ptr.as_ptr(), self.len()))
+        }
+    }
+
+    /// Get a raw pointer to the `StringCacheEntry`.
+    #[inline]
+    fn as_string_cache_entry(&self) -> &StringCacheEntry {
+        // The allocator guarantees that the alignment is correct and that
+        // this pointer is non-null
+        unsafe { &*(self.char_ptr.as_ptr().cast::<StringCacheEntry>().sub(1)) }
+    }
+
+    /// Get the length (in bytes) of this string.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.as_string_cache_entry().len
+    }
+
+    /// Returns `true` if this is the empty string.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the precomputed hash for this string.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        Digest {
+            hash: self.as_string_cache_entry().hash,
+        }
+    }
+}
+
+// The strings are immutable, and the interner they borrow from is `Sync`.
+unsafe impl Send for LocalEstr<'_> {}
+unsafe impl Sync for LocalEstr<'_> {}
+
+impl PartialEq for LocalEstr<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        // Handles from different interners may share a lifetime, so fall back
+        // to comparing contents when the pointers differ.
+        self.char_ptr == other.char_ptr
+            || (self.digest() == other.digest() && self.as_str() == other.as_str())
+    }
+}
+
+impl Eq for LocalEstr<'_> {}
+
+impl PartialOrd for LocalEstr<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LocalEstr<'_> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.digest()
+            .cmp(&other.digest())
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+impl PartialEq<str> for LocalEstr<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for LocalEstr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl AsRef<str> for LocalEstr<'_> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl ops::Deref for LocalEstr<'_> {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for LocalEstr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for LocalEstr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl hash::Hash for LocalEstr<'_> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.digest().hash.hash(state);
+    }
+}
@@ -0,0 +1,111 @@
+use core::{borrow, cmp, fmt, hash, ops};
+
+use crate::Estr;
+
+/// An `Estr` that orders lexicographically.
+///
+/// `Estr` orders by hash, which is cheap but meaningless to a reader. Wrap it
+/// in a `LexEstr` to sort by the contents of the string instead, for example
+/// as the key of a [`LexEstrMap`](crate::LexEstrMap).
+///
+/// # Examples
+///
+/// ```
+/// use estr::{LexEstr, LexEstrMap, estr};
+///
+/// let mut words: Vec<LexEstr> = ["quick", "brown", "fox"]
+///     .into_iter()
+///     .map(|w| LexEstr(estr(w)))
+///     .collect();
+/// words.sort();
+/// assert_eq!(words, [estr("brown"), estr("fox"), estr("quick")]);
+///
+/// let mut counts = LexEstrMap::new();
+/// counts.insert(LexEstr(estr("fox")), 1);
+/// assert_eq!(counts.get("fox"), Some(&1));
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct LexEstr(pub Estr);
+
+impl LexEstr {
+    /// Get the cached `Estr` as a `str`.
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        self.0.as_str()
+    }
+}
+
+impl PartialOrd for LexEstr {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LexEstr {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp_str(&other.0)
+    }
+}
+
+// Hash the contents rather than the digest, so that `Borrow<str>` is valid.
+impl hash::Hash for LexEstr {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl borrow::Borrow<str> for LexEstr {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<Estr> for LexEstr {
+    fn eq(&self, other: &Estr) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<LexEstr> for Estr {
+    fn eq(&self, other: &LexEstr) -> bool {
+        *self == other.0
+    }
+}
+
+impl From<Estr> for LexEstr {
+    fn from(e: Estr) -> LexEstr {
+        LexEstr(e)
+    }
+}
+
+impl From<LexEstr> for Estr {
+    fn from(l: LexEstr) -> Estr {
+        l.0
+    }
+}
+
+impl From<&str> for LexEstr {
+    fn from(s: &str) -> LexEstr {
+        LexEstr(Estr::from(s))
+    }
+}
+
+impl ops::Deref for LexEstr {
+    type Target = Estr;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Display for LexEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for LexEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
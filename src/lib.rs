@@ -5,12 +5,42 @@ extern crate std;
 
 extern crate alloc;
 
+mod arena;
 mod bumpalloc;
+mod bytes;
 mod cfg;
+mod ci;
 mod collections;
+mod config;
+mod error;
+mod front;
+mod interner;
+mod lex;
+mod maybe;
+mod small;
+pub mod snapshot;
 mod stringcache;
 
+cfg::serde! {
+    mod serialization;
+}
+
+cfg::std! {
+    mod path;
+    pub use path::{EOsStr, EPath};
+}
+
+pub use arena::{HeapArena, StaticArena};
+pub use bytes::EBytes;
+pub use ci::EstrCi;
 pub use collections::*;
+pub use config::{CacheConfig, configure};
+pub use error::{InternError, SnapshotError};
+pub use interner::{Interner, LocalEstr};
+pub use lex::LexEstr;
+pub use maybe::MaybeEstr;
+pub use small::SmallEstr;
+pub use stringcache::{BinStats, CacheStats, StringCacheIterator};
 
 mod platform {
     use crate::cfg;
@@ -28,10 +58,10 @@ mod platform {
     }
 }
 
-use alloc::{borrow, boxed, fmt, rc, slice, str, string, sync};
-use core::{cell, cmp, hash, ops, ptr};
+use alloc::{borrow, boxed, fmt, rc, slice, str, string, sync, vec};
+use core::{cmp, ffi, hash, ops, ptr, sync::atomic};
 
-use crate::platform::Mutex;
+use crate::config::config;
 use crate::stringcache::*;
 
 /// A handle representing a string in the global string cache.
@@ -44,7 +74,12 @@ pub struct Estr {
 impl Estr {
     /// Create a new `Estr` from the given `str`.
     ///
-    /// You can also use the [`estr`] function.
+    /// You can also use the [`estr()`] function.
+    ///
+    /// With the `thread-cache` feature, each thread remembers the strings it
+    /// interned most recently, so interning them again does not lock the
+    /// cache. This helps when many threads keep interning the same hot
+    /// strings.
     ///
     /// # Examples
     ///
@@ -55,20 +90,210 @@ impl Estr {
     /// let e2 = estr("the quick brown fox");
     /// assert_eq!(e1, e2);
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache runs out of memory or goes over its budget. Use
+    /// [`Estr::try_from_str`] to handle that instead.
     pub fn from(string: &str) -> Estr {
-        let Digest { hash } = digest(string);
-        let mut sc = STRING_CACHE[whichbin(hash)].lock();
-        let ptr = sc.insert(string, hash);
-        Estr {
+        Estr::insert(string, digest(string)).expect("failed to intern string")
+    }
+
+    /// Create a new `Estr` from the given `str`, or return an error if the
+    /// cache runs out of memory or would go over the budget set with
+    /// [`configure`].
+    ///
+    /// A failed attempt leaves the cache unchanged, and later attempts may
+    /// succeed if memory becomes available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::{CacheConfig, Estr, InternError, StaticArena, configure, estr};
+    ///
+    /// static mut POOL: [u8; 1024] = [0; 1024];
+    /// static ARENA: StaticArena = StaticArena::new(unsafe { &mut *(&raw mut POOL) });
+    ///
+    /// configure(CacheConfig {
+    ///     bins: 1,
+    ///     initial_alloc: 256,
+    ///     arena: &ARENA,
+    ///     ..CacheConfig::DEFAULT
+    /// })
+    /// .unwrap();
+    ///
+    /// let fox = Estr::try_from_str("the quick brown fox").unwrap();
+    /// let long = "the quick brown fox ".repeat(100);
+    /// assert_eq!(Estr::try_from_str(&long), Err(InternError::OutOfMemory));
+    /// assert_eq!(Estr::try_from_str("the quick brown fox"), Ok(fox));
+    /// assert_eq!(estr("the lazy dog"), "the lazy dog");
+    /// ```
+    pub fn try_from_str(string: &str) -> Result<Estr, InternError> {
+        Estr::insert(string, digest(string))
+    }
+
+    /// Create a new `Estr` from formatted text, without allocating for short
+    /// results.
+    ///
+    /// The text is formatted into a buffer on the stack, and only spills to
+    /// the heap if it is longer than a few hundred bytes. Arguments that need
+    /// no formatting at all are interned directly. You will usually want the
+    /// [`estr_format!`] macro, which calls this for you.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache runs out of memory or goes over its budget, or if a
+    /// formatting trait implementation returns an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::{Estr, estr};
+    ///
+    /// let module = "core";
+    /// let item = estr("fmt");
+    /// let path = Estr::from_fmt(format_args!("{module}::{item}"));
+    /// assert_eq!(path, estr("core::fmt"));
+    /// ```
+    pub fn from_fmt(args: fmt::Arguments<'_>) -> Estr {
+        if let Some(string) = args.as_str() {
+            return Estr::from(string);
+        }
+        let mut buf = FmtBuffer::new();
+        fmt::Write::write_fmt(&mut buf, args)
+            .expect("a formatting trait implementation returned an error");
+        Estr::from(buf.as_str())
+    }
+
+    /// Create a new `Estr` from the given `str` and its already computed
+    /// [`Digest`], skipping the hash.
+    ///
+    /// The digest must be the one returned by [`digest()`] for this string. This
+    /// is checked in debug builds. In release builds a wrong digest is not
+    /// unsafe, but the string will not be found by later lookups and maps
+    /// keyed by it will misbehave.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if `digest` does not match `string`, and if the
+    /// cache runs out of memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::{Digest, Estr, digest, estr};
+    ///
+    /// const FOX: Digest = digest!("the quick brown fox");
+    ///
+    /// let e1 = Estr::from_with_digest("the quick brown fox", FOX);
+    /// let e2 = estr("the quick brown fox");
+    /// assert_eq!(e1, e2);
+    /// ```
+    pub fn from_with_digest(string: &str, digest: Digest) -> Estr {
+        debug_assert!(
+            digest == crate::digest(string),
+            "digest does not match the string {string:?}"
+        );
+        Estr::insert(string, digest).expect("failed to intern string")
+    }
+
+    fn insert(string: &str, digest: Digest) -> Result<Estr, InternError> {
+        if let Some(e) = front::get(string, digest) {
+            return Ok(e);
+        }
+        let Digest { hash } = digest;
+        let ptr = global_bin(hash).with_cache(StringCache::new, |sc| {
+            sc.insert(string.as_bytes(), hash, &USAGE)
+        })?;
+        let e = Estr {
             // SAFETY: sc.insert does not give back a null pointer
             char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+        };
+        front::put(e);
+        Ok(e)
+    }
+
+    /// Get the `Estr` for a compile-time entry.
+    ///
+    /// The first call looks the string up in the global cache without a lock,
+    /// and only if it is missing registers the entry, without copying or
+    /// hashing the string. Later calls only load the handle remembered by the
+    /// entry. You will usually want the [`estr!`] macro, which builds the
+    /// [`StaticEstr`] for you.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry has to be registered and the cache runs out of
+    /// memory or goes over its budget.
+    #[inline]
+    pub fn from_static<const N: usize>(entry: &'static StaticEstr<N>) -> Estr {
+        match ptr::NonNull::new(entry.canonical.load(atomic::Ordering::Acquire)) {
+            Some(char_ptr) => Estr { char_ptr },
+            None => {
+                // Take the header pointer from the whole `StaticEstr`, so that
+                // it may be used to reach the characters after the header.
+                let whole = entry as *const StaticEstr<N>;
+                // SAFETY: `whole` comes from a reference, so it is valid.
+                let header = unsafe { &raw const (*whole).entry };
+                Estr::register_static(header, &entry.canonical)
+            }
+        }
+    }
+
+    #[cold]
+    fn register_static(
+        entry: *const StringCacheEntry,
+        canonical: &'static atomic::AtomicPtr<u8>,
+    ) -> Estr {
+        // The characters directly follow the header, exactly as they do for
+        // entries in the cache, and the entry lives forever.
+        // SAFETY: the entry is a field of a `StaticEstr` in a `static`.
+        let (hash, len) = unsafe { ((*entry).hash, (*entry).len) };
+        let string = unsafe { slice::from_raw_parts(entry.add(1).cast::<u8>(), len) };
+        let bin = global_bin(hash);
+        let ptr = match bin.get_existing(string, hash) {
+            Some(ptr) => ptr,
+            None => bin
+                .with_cache(StringCache::new, |sc| sc.insert_entry(entry, &USAGE))
+                .expect("failed to intern string"),
+        };
+        // Racing threads all find the same handle, so it does not matter which
+        // of them stores it.
+        canonical.store(ptr as *mut u8, atomic::Ordering::Release);
+        Estr {
+            // SAFETY: sc.insert_entry does not give back a null pointer
+            char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
         }
     }
 
     pub fn from_existing(string: &str) -> Option<Estr> {
         let Digest { hash } = digest(string);
-        let sc = STRING_CACHE[whichbin(hash)].lock();
-        sc.get_existing(string, hash).map(|ptr| Estr {
+        global_bin(hash)
+            .get_existing(string.as_bytes(), hash)
+            .map(|ptr| Estr {
+                char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+            })
+    }
+
+    /// Find the `Estr` with the given hash, if one exists in the cache.
+    ///
+    /// This lets a string be recovered from just its [`Digest`], for example
+    /// when a protocol sends hashes in place of strings. Two different strings
+    /// may in principle share a digest. If that happens, this returns one of
+    /// them, so check the result when a collision would be a problem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::{Estr, digest, estr};
+    ///
+    /// let fox = estr("the quick brown fox");
+    /// assert_eq!(Estr::from_digest(fox.digest()), Some(fox));
+    /// assert_eq!(Estr::from_digest(digest("the lazy dog")), None);
+    /// ```
+    pub fn from_digest(digest: Digest) -> Option<Estr> {
+        let Digest { hash } = digest;
+        global_bin(hash).get_by_hash(hash).map(|ptr| Estr {
             char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
         })
     }
@@ -96,6 +321,38 @@ impl Estr {
         }
     }
 
+    /// Get the cached `Estr` as a null-terminated C string.
+    ///
+    /// Every string in the cache is stored with a trailing null byte, so this
+    /// does not allocate. It does have to scan the string, and returns an
+    /// error if the string contains a null byte of its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::estr;
+    ///
+    /// let fox = estr("the quick brown fox");
+    /// assert_eq!(fox.as_c_str().unwrap(), c"the quick brown fox");
+    /// assert!(estr("nul\0byte").as_c_str().is_err());
+    /// ```
+    pub fn as_c_str(&self) -> Result<&'static ffi::CStr, ffi::FromBytesWithNulError> {
+        // This is safe for the same reasons as `as_str`, and because the null
+        // terminator directly follows the string.
+        let bytes = unsafe { slice::from_raw_parts(self.char_ptr.as_ptr(), self.len() + 1) };
+        ffi::CStr::from_bytes_with_nul(bytes)
+    }
+
+    /// Get a pointer to the null-terminated string, for passing to C.
+    ///
+    /// This does not check for null bytes inside the string, which C code
+    /// would see as the end of the string. Use [`Estr::as_c_str`] if that
+    /// matters.
+    #[inline]
+    pub fn as_char_ptr(&self) -> *const ffi::c_char {
+        self.char_ptr.as_ptr() as *const ffi::c_char
+    }
+
     /// Get a raw pointer to the `StringCacheEntry`.
     #[inline]
     fn as_string_cache_entry(&self) -> &StringCacheEntry {
@@ -110,6 +367,12 @@ impl Estr {
         self.as_string_cache_entry().len
     }
 
+    /// Returns `true` if this is the empty string.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Get the precomputed hash for this string.
     #[inline]
     pub fn digest(&self) -> Digest {
@@ -118,6 +381,29 @@ impl Estr {
         }
     }
 
+    /// Compare the contents of two strings lexicographically.
+    ///
+    /// The `Ord` implementation for `Estr` compares hashes, which is fast but
+    /// gives an arbitrary order. Use this, or the [`LexEstr`] wrapper, when
+    /// the order will be seen by a person.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::estr;
+    ///
+    /// let mut words = [estr("quick"), estr("brown"), estr("fox")];
+    /// words.sort_by(|a, b| a.cmp_str(b));
+    /// assert_eq!(words, ["brown", "fox", "quick"]);
+    /// ```
+    #[inline]
+    pub fn cmp_str(&self, other: &Estr) -> cmp::Ordering {
+        if self.char_ptr == other.char_ptr {
+            return cmp::Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+
     /// Get an owned String copy of this string.
     pub fn to_owned(&self) -> string::String {
         string::ToString::to_string(&self.as_str())
@@ -138,7 +424,9 @@ impl PartialOrd for Estr {
 
 impl Ord for Estr {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.digest().cmp(&other.digest())
+        self.digest()
+            .cmp(&other.digest())
+            .then_with(|| self.as_str().cmp(other.as_str()))
     }
 }
 
@@ -221,24 +509,28 @@ impl PartialEq<Estr> for &boxed::Box<str> {
 }
 
 impl PartialEq<borrow::Cow<'_, str>> for Estr {
+    #[allow(clippy::op_ref, clippy::borrow_deref_ref)]
     fn eq(&self, other: &borrow::Cow<'_, str>) -> bool {
         self.as_str() == &*other
     }
 }
 
 impl PartialEq<Estr> for borrow::Cow<'_, str> {
+    #[allow(clippy::op_ref, clippy::borrow_deref_ref)]
     fn eq(&self, u: &Estr) -> bool {
         &*self == u.as_str()
     }
 }
 
 impl PartialEq<&borrow::Cow<'_, str>> for Estr {
+    #[allow(clippy::op_ref, clippy::borrow_deref_ref)]
     fn eq(&self, other: &&borrow::Cow<'_, str>) -> bool {
         self.as_str() == &**other
     }
 }
 
 impl PartialEq<Estr> for &borrow::Cow<'_, str> {
+    #[allow(clippy::op_ref, clippy::borrow_deref_ref)]
     fn eq(&self, u: &Estr) -> bool {
         &**self == u.as_str()
     }
@@ -285,9 +577,11 @@ where
 impl str::FromStr for Estr {
     type Err = ();
 
+    /// Interns the string, failing if the cache runs out of memory or would
+    /// go over its budget.
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Estr::from(s))
+        Estr::try_from_str(s).map_err(|_| ())
     }
 }
 
@@ -340,30 +634,35 @@ impl From<string::String> for Estr {
 }
 
 impl From<&string::String> for Estr {
+    #[allow(clippy::explicit_auto_deref)]
     fn from(s: &string::String) -> Estr {
         Estr::from(&**s)
     }
 }
 
 impl From<boxed::Box<str>> for Estr {
+    #[allow(clippy::explicit_auto_deref)]
     fn from(s: boxed::Box<str>) -> Estr {
         Estr::from(&*s)
     }
 }
 
 impl From<rc::Rc<str>> for Estr {
+    #[allow(clippy::explicit_auto_deref)]
     fn from(s: rc::Rc<str>) -> Estr {
         Estr::from(&*s)
     }
 }
 
 impl From<sync::Arc<str>> for Estr {
+    #[allow(clippy::explicit_auto_deref)]
     fn from(s: sync::Arc<str>) -> Estr {
         Estr::from(&*s)
     }
 }
 
 impl From<borrow::Cow<'_, str>> for Estr {
+    #[allow(clippy::explicit_auto_deref)]
     fn from(s: borrow::Cow<'_, str>) -> Estr {
         Estr::from(&*s)
     }
@@ -400,16 +699,212 @@ impl hash::Hash for Estr {
     }
 }
 
+/// Storage for an `Estr` whose string is known at compile time.
+///
+/// This holds an entry laid out like those in the string cache, which lets it
+/// live in a `static` and be registered in the cache by
+/// [`Estr::from_static`], along with the handle it was registered as. `N` is
+/// the length of the string plus one for the null terminator. Prefer the
+/// [`estr!`] macro over naming this type directly.
+#[repr(C)]
+pub struct StaticEstr<const N: usize> {
+    canonical: atomic::AtomicPtr<u8>,
+    entry: StringCacheEntry,
+    chars: [u8; N],
+}
+
+impl<const N: usize> StaticEstr<N> {
+    /// Create the entry for `string`, hashing it at compile time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not `string.len() + 1`.
+    pub const fn new(string: &str) -> StaticEstr<N> {
+        assert!(
+            string.len() + 1 == N,
+            "N must be one more than the string length"
+        );
+        let mut chars = [0u8; N];
+        chars
+            .split_at_mut(string.len())
+            .0
+            .copy_from_slice(string.as_bytes());
+        StaticEstr {
+            canonical: atomic::AtomicPtr::new(ptr::null_mut()),
+            entry: StringCacheEntry {
+                hash: digest(string).hash,
+                len: string.len(),
+            },
+            chars,
+        }
+    }
+}
+
+/// Create an `Estr` from formatted text, like `format!`.
+///
+/// This formats into a buffer on the stack rather than a `String`, so
+/// interning a short generated name costs one hash and one copy into the
+/// cache. See [`Estr::from_fmt`].
+///
+/// # Examples
+///
+/// ```
+/// use estr::{estr, estr_format};
+///
+/// let module = estr("collections");
+/// let names: Vec<_> = ["EstrMap", "EstrSet"]
+///     .iter()
+///     .map(|item| estr_format!("{module}::{item}"))
+///     .collect();
+/// assert_eq!(names, [estr("collections::EstrMap"), estr("collections::EstrSet")]);
+///
+/// let long = estr_format!("{:>1000}", "fox");
+/// assert_eq!(long.len(), 1000);
+/// ```
+#[macro_export]
+macro_rules! estr_format {
+    ($($arg:tt)*) => {
+        $crate::Estr::from_fmt(::core::format_args!($($arg)*))
+    };
+}
+
+// A buffer for `Estr::from_fmt` that starts on the stack and moves to the
+// heap if the text outgrows it.
+struct FmtBuffer {
+    len: usize,
+    stack: [u8; FmtBuffer::STACK_LEN],
+    heap: string::String,
+}
+
+impl FmtBuffer {
+    const STACK_LEN: usize = 256;
+
+    fn new() -> FmtBuffer {
+        FmtBuffer {
+            len: 0,
+            stack: [0; FmtBuffer::STACK_LEN],
+            heap: string::String::new(),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        if self.len <= FmtBuffer::STACK_LEN {
+            // SAFETY: the stack buffer is only ever filled with whole `str`s.
+            unsafe { str::from_utf8_unchecked(&self.stack[..self.len]) }
+        } else {
+            &self.heap
+        }
+    }
+}
+
+impl fmt::Write for FmtBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.len + s.len();
+        if len <= FmtBuffer::STACK_LEN {
+            self.stack[self.len..len].copy_from_slice(s.as_bytes());
+        } else {
+            if self.len <= FmtBuffer::STACK_LEN {
+                // SAFETY: as in `as_str`.
+                let spilled = unsafe { str::from_utf8_unchecked(&self.stack[..self.len]) };
+                self.heap.reserve(len);
+                self.heap.push_str(spilled);
+            }
+            self.heap.push_str(s);
+        }
+        self.len = len;
+        Ok(())
+    }
+}
+
+/// Create an `Estr` from a string literal, hashed at compile time.
+///
+/// The string and its hash are stored in a `static`, which is registered in
+/// the global cache the first time the expression is evaluated. After that it
+/// costs a single atomic load and never takes a lock. The handle is the same
+/// as any `Estr` created at runtime from the same string, so comparing them is
+/// a pointer comparison.
+///
+/// To name a handle once and use it from anywhere, declare it as a `static`
+/// with `estr!(static NAME = "...")`. This defines a [`StaticEstr`], which is
+/// built at compile time, and [`Estr::from_static`] turns it into the handle.
+/// The handle itself is only known once registered, so it cannot be a `const`
+/// or a pattern. Match on the [`Digest`] instead, and confirm the string in a
+/// guard so that a hash collision cannot pick the wrong arm.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{Digest, Estr, digest, estr, existing_estr};
+///
+/// estr!(pub static FOX = "the quick brown fox");
+///
+/// let fox = Estr::from_static(&FOX);
+/// assert_eq!(fox, estr("the quick brown fox"));
+/// assert_eq!(estr!("the quick brown fox"), fox);
+/// assert_eq!(existing_estr("the quick brown fox"), Some(fox));
+///
+/// const DOG: Digest = digest("the lazy dog");
+/// let animal = |e: Estr| match e.digest() {
+///     DOG if e == "the lazy dog" => "dog",
+///     _ if e == fox => "fox",
+///     _ => "unknown",
+/// };
+/// assert_eq!(animal(estr("the lazy dog")), "dog");
+/// assert_eq!(animal(fox), "fox");
+/// assert_eq!(animal(estr("the lazy cat")), "unknown");
+/// ```
+#[macro_export]
+macro_rules! estr {
+    ($(#[$attr:meta])* $vis:vis static $name:ident = $string:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::StaticEstr<{ $string.len() + 1 }> =
+            $crate::StaticEstr::new($string);
+    };
+    ($string:expr) => {{
+        const STRING: &str = $string;
+        static ENTRY: $crate::StaticEstr<{ STRING.len() + 1 }> = $crate::StaticEstr::new(STRING);
+        $crate::Estr::from_static(&ENTRY)
+    }};
+}
+
+/// Compute the [`Digest`] of a string at compile time.
+///
+/// This is the same as calling [`digest()`], except that the result is always
+/// computed during compilation, even outside a `const` context.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{digest, estr};
+///
+/// assert_eq!(digest!("the quick brown fox"), estr("the quick brown fox").digest());
+/// ```
+#[macro_export]
+macro_rules! digest {
+    ($string:expr) => {{
+        const DIGEST: $crate::Digest = $crate::digest($string);
+        DIGEST
+    }};
+}
+
 #[inline(always)]
 pub const fn digest(string: &str) -> Digest {
+    digest_bytes(string.as_bytes())
+}
+
+/// Compute the [`Digest`] of a byte string, as used by [`EBytes`].
+///
+/// This is the same as the digest of a `str` with the same bytes.
+#[inline(always)]
+pub const fn digest_bytes(bytes: &[u8]) -> Digest {
     let hash = rapidhash::v3::rapidhash_v3_nano_inline::<true, false>;
     let seed = &rapidhash::v3::DEFAULT_RAPID_SECRETS;
     Digest {
-        hash: hash(string.as_bytes(), seed),
+        hash: hash(bytes, seed),
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Digest {
     hash: u64,
 }
@@ -450,12 +945,85 @@ impl hash::Hash for Digest {
     }
 }
 
+/// Looking up an `Estr` by `Digest` only compares hashes, so it can return the
+/// entry for a different string if two strings collide. Use [`DigestedStr`]
+/// when that matters.
 impl hashbrown::Equivalent<Estr> for Digest {
     fn equivalent(&self, key: &Estr) -> bool {
         key.digest().hash == self.hash
     }
 }
 
+/// A `str` paired with its precomputed [`Digest`].
+///
+/// This can be used to look up keys in an [`EstrMap`] or [`EstrSet`] without
+/// hashing the string again. Unlike looking up by a bare `Digest`, the string
+/// is compared as well, so a hash collision cannot return the wrong entry.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{DigestedStr, EstrMap, estr};
+///
+/// const FOX: DigestedStr = DigestedStr::new("fox");
+///
+/// let mut map = EstrMap::default();
+/// map.insert(estr("fox"), 1);
+/// assert_eq!(map.get(&FOX), Some(&1));
+/// assert_eq!(map.get(&DigestedStr::new("dog")), None);
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DigestedStr<'a> {
+    digest: Digest,
+    string: &'a str,
+}
+
+impl<'a> DigestedStr<'a> {
+    /// Hash the given `str`. This can be done at compile time.
+    pub const fn new(string: &'a str) -> DigestedStr<'a> {
+        DigestedStr {
+            digest: digest(string),
+            string,
+        }
+    }
+
+    /// Get the string.
+    #[inline]
+    pub fn as_str(&self) -> &'a str {
+        self.string
+    }
+
+    /// Get the precomputed hash for the string.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        self.digest
+    }
+}
+
+impl hash::Hash for DigestedStr<'_> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.digest.hash.hash(state);
+    }
+}
+
+impl hashbrown::Equivalent<Estr> for DigestedStr<'_> {
+    fn equivalent(&self, key: &Estr) -> bool {
+        key.digest() == self.digest && key.as_str() == self.string
+    }
+}
+
+impl PartialEq<DigestedStr<'_>> for Estr {
+    fn eq(&self, other: &DigestedStr<'_>) -> bool {
+        self.digest() == other.digest && self.as_str() == other.string
+    }
+}
+
+impl PartialEq<Estr> for DigestedStr<'_> {
+    fn eq(&self, other: &Estr) -> bool {
+        other == self
+    }
+}
+
 /// Create a new `Estr` from the given `str`.
 ///
 /// # Examples
@@ -486,16 +1054,277 @@ pub fn estr(s: &str) -> Estr {
 /// assert_eq!(e1, None);
 /// assert_eq!(e3, Some(e2));
 /// ```
+///
+/// Lookups never take a lock, so they see a consistent table even while
+/// another thread is inserting strings and growing it.
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::thread;
+/// use estr::{CacheConfig, Estr, cache_stats, configure, digest, estr, existing_estr};
+///
+/// configure(CacheConfig {
+///     bins: 1,
+///     initial_capacity: 16,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// let strings: Vec<String> = (0..10_000).map(|i| format!("string {i}")).collect();
+/// let done = AtomicBool::new(false);
+/// thread::scope(|scope| {
+///     for t in 0..3 {
+///         let (strings, done) = (&strings, &done);
+///         scope.spawn(move || {
+///             let mut i = t;
+///             while !done.load(Ordering::Acquire) {
+///                 let s = &strings[i % strings.len()];
+///                 if let Some(e) = existing_estr(s) {
+///                     assert_eq!(e.as_str(), s);
+///                 }
+///                 if let Some(e) = Estr::from_digest(digest(s)) {
+///                     assert_eq!(e.as_str(), s);
+///                 }
+///                 i += 7;
+///             }
+///         });
+///     }
+///     for s in &strings {
+///         estr(s);
+///     }
+///     done.store(true, Ordering::Release);
+/// });
+///
+/// assert!(strings.iter().all(|s| existing_estr(s).is_some_and(|e| e == s.as_str())));
+/// assert!(cache_stats().table_capacity >= 16 << 9);
+/// ```
 #[inline]
 pub fn existing_estr(s: &str) -> Option<Estr> {
     Estr::from_existing(s)
 }
 
-static STRING_CACHE: [Mutex<cell::LazyCell<StringCache>>; NUM_BINS] =
-    [const { Mutex::new(cell::LazyCell::new(StringCache::new)) }; NUM_BINS];
+/// Create an `Estr` for each of the given strings, in order.
+///
+/// This gives the same result as calling [`estr()`] on each string, but is
+/// faster for large batches. All the strings are hashed up front and grouped
+/// by bin, so that each bin is locked at most once.
+///
+/// # Panics
+///
+/// Panics if the cache runs out of memory or goes over its budget. Use
+/// [`try_intern_batch`] to handle that instead.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{estr, intern_batch};
+///
+/// let words = intern_batch(&["the", "quick", "brown", "fox"]);
+/// assert_eq!(words, [estr("the"), estr("quick"), estr("brown"), estr("fox")]);
+/// ```
+pub fn intern_batch(strings: &[&str]) -> vec::Vec<Estr> {
+    try_intern_batch(strings).expect("failed to intern string")
+}
+
+/// Create an `Estr` for each of the given strings, in order, or return an
+/// error if the cache runs out of memory or would go over its budget.
+///
+/// Some of the strings may have been interned by the time an error is
+/// returned.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{CacheConfig, InternError, configure, try_intern_batch};
+///
+/// configure(CacheConfig {
+///     max_entries: 3,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// assert!(try_intern_batch(&["the", "quick", "fox"]).is_ok());
+/// assert_eq!(
+///     try_intern_batch(&["the", "quick", "brown", "fox"]),
+///     Err(InternError::BudgetExceeded)
+/// );
+/// ```
+pub fn try_intern_batch(strings: &[&str]) -> Result<vec::Vec<Estr>, InternError> {
+    let mut estrs = vec::Vec::with_capacity(strings.len());
+    try_intern_batch_into(strings.iter().copied(), &mut estrs)?;
+    Ok(estrs)
+}
+
+/// Intern each of the given strings, in order, and add them to `out`.
+///
+/// This is like [`intern_batch`], but accepts any iterator of strings and any
+/// collection that implements `Extend`.
+///
+/// # Panics
+///
+/// Panics if the cache runs out of memory or goes over its budget, in which
+/// case nothing is added to `out`. Use [`try_intern_batch_into`] to handle
+/// that instead.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{EstrSet, estr, intern_batch_into};
+///
+/// let mut vocabulary = EstrSet::default();
+/// intern_batch_into("the quick brown fox".split(' '), &mut vocabulary);
+/// assert!(vocabulary.contains(&estr("fox")));
+/// ```
+pub fn intern_batch_into<'a, I, E>(strings: I, out: &mut E)
+where
+    I: IntoIterator<Item = &'a str>,
+    E: Extend<Estr>,
+{
+    try_intern_batch_into(strings, out).expect("failed to intern string");
+}
+
+/// Intern each of the given strings, in order, and add them to `out`, or
+/// return an error if the cache runs out of memory or would go over its
+/// budget.
+///
+/// Nothing is added to `out` if an error is returned, but some of the strings
+/// may have been interned by then.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{CacheConfig, EstrSet, InternError, configure, try_intern_batch_into};
+///
+/// configure(CacheConfig {
+///     max_entries: 3,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// let mut vocabulary = EstrSet::default();
+/// assert!(try_intern_batch_into("the quick fox".split(' '), &mut vocabulary).is_ok());
+/// assert_eq!(
+///     try_intern_batch_into("the quick brown fox".split(' '), &mut vocabulary),
+///     Err(InternError::BudgetExceeded)
+/// );
+/// assert_eq!(vocabulary.len(), 3);
+/// ```
+pub fn try_intern_batch_into<'a, I, E>(strings: I, out: &mut E) -> Result<(), InternError>
+where
+    I: IntoIterator<Item = &'a str>,
+    E: Extend<Estr>,
+{
+    let strings: vec::Vec<(u64, &str)> = strings
+        .into_iter()
+        .map(|string| (digest(string).hash, string))
+        .collect();
+    let ptrs = insert_hashed(&strings)?;
+    out.extend(ptrs.into_iter().map(|ptr| Estr {
+        // SAFETY: sc.insert does not give back a null pointer
+        char_ptr: unsafe { ptr::NonNull::new_unchecked(ptr as *mut _) },
+    }));
+    Ok(())
+}
+
+// Insert strings with known hashes into the global cache, returning pointers
+// to their characters in the same order.
+fn insert_hashed(strings: &[(u64, &str)]) -> Result<vec::Vec<*const u8>, InternError> {
+    insert_grouped(strings, |sc, hash, string| {
+        sc.insert(string.as_bytes(), hash, &USAGE)
+    })
+}
+
+// Add strings with known hashes to the global cache with `insert`, visiting
+// them grouped by bin so each lock is taken once.
+fn insert_grouped(
+    strings: &[(u64, &str)],
+    mut insert: impl FnMut(&mut StringCache, u64, &str) -> Result<*const u8, InternError>,
+) -> Result<vec::Vec<*const u8>, InternError> {
+    let num_bins = config().bins;
+    let mut order: vec::Vec<(usize, usize)> = strings
+        .iter()
+        .enumerate()
+        .map(|(index, &(hash, _))| (whichbin(hash, num_bins), index))
+        .collect();
+    order.sort_unstable();
+
+    let mut ptrs = vec![ptr::null(); strings.len()];
+    for group in order.chunk_by(|a, b| a.0 == b.0) {
+        STRING_CACHE[group[0].0].with_cache(StringCache::new, |sc| {
+            for &(_, index) in group {
+                let (hash, string) = strings[index];
+                ptrs[index] = insert(sc, hash, string)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(ptrs)
+}
+
+/// Iterate over every string in the global string cache.
+///
+/// The iterator walks a snapshot of the cache taken when this is called.
+/// Strings interned afterwards may not be visited. Strings are yielded in no
+/// particular order.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{cache_iter, estr};
+///
+/// let fox = estr("the quick brown fox");
+/// assert!(cache_iter().any(|e| e == fox));
+/// ```
+pub fn cache_iter() -> StringCacheIterator {
+    let mut chunks = vec::Vec::new();
+    for bin in STRING_CACHE.iter() {
+        bin.with_existing_cache(|sc| chunks.extend(sc.chunks()));
+    }
+    StringCacheIterator::new(chunks)
+}
+
+/// Collect statistics about the memory used by the global string cache.
+///
+/// This briefly locks each bin in turn and walks its table, so it is not
+/// intended to be called on a hot path.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{cache_stats, estr};
+///
+/// estr("the quick brown fox");
+/// let stats = cache_stats();
+/// assert!(stats.entries >= 1);
+/// assert!(stats.string_bytes >= "the quick brown fox".len());
+/// assert!(stats.allocated_bytes <= stats.capacity_bytes);
+/// ```
+pub fn cache_stats() -> CacheStats {
+    let bins = STRING_CACHE
+        .iter()
+        .map(|bin| {
+            bin.with_existing_cache(StringCache::stats)
+                .unwrap_or_default()
+        })
+        .collect();
+    CacheStats::from_bins(bins)
+}
+
+// Bins are created on first use, so that looking up or iterating an empty
+// cache does not allocate.
+static STRING_CACHE: [Bin; NUM_BINS] = [const { Bin::new() }; NUM_BINS];
+
+// How much of the budget in the global config the bins have used together.
+static USAGE: Usage = Usage::new();
+
+// Use the top bits of the hash to choose one of `num_bins` bins, which must be
+// a power of two no greater than `NUM_BINS`.
+#[inline]
+fn whichbin(hash: u64, num_bins: usize) -> usize {
+    ((hash >> TOP_SHIFT as u64) % NUM_BINS as u64) as usize & (num_bins - 1)
+}
 
-// Use the top bits of the hash to choose a bin
 #[inline]
-fn whichbin(hash: u64) -> usize {
-    ((hash >> TOP_SHIFT as u64) % NUM_BINS as u64) as usize
+fn global_bin(hash: u64) -> &'static Bin {
+    &STRING_CACHE[whichbin(hash, config().bins)]
 }
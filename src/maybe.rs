@@ -0,0 +1,237 @@
+use alloc::{string, sync};
+use core::{cmp, fmt, hash, ops};
+
+use crate::{Digest, Estr, digest};
+
+/// A string that is only interned when that is likely to pay off.
+///
+/// Every string given to [`Estr`] stays in the global cache for the rest of
+/// the program. That is a good trade for identifiers and keywords, which are
+/// short and repeat often, but a poor one for long, one-off strings such as
+/// messages or file contents. A `MaybeEstr` interns a string only if it is at
+/// most [`MaybeEstr::DEFAULT_MAX_LEN`] bytes long, or is already in the cache,
+/// and otherwise keeps it in a reference-counted `Arc<str>` that is freed with
+/// its last handle. Short strings are also kept owned if the cache is over its
+/// [budget](crate::CacheConfig::max_bytes).
+///
+/// Both kinds of string compare, hash and order by their contents in the same
+/// way, so they can be mixed freely as map keys.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{MaybeEstr, estr, existing_estr};
+///
+/// let short = MaybeEstr::new("fox");
+/// assert!(short.is_interned());
+/// assert_eq!(short, estr("fox"));
+///
+/// let long = "the quick brown fox jumps over the lazy dog ".repeat(10);
+/// let owned = MaybeEstr::new(&long);
+/// assert!(!owned.is_interned());
+/// assert_eq!(existing_estr(&long), None);
+///
+/// // Long strings are still shared if someone already interned them.
+/// estr(&long);
+/// assert_eq!(MaybeEstr::new(&long), owned);
+/// assert!(MaybeEstr::new(&long).is_interned());
+/// ```
+#[derive(Clone)]
+pub enum MaybeEstr {
+    /// A string in the global cache.
+    Interned(Estr),
+    /// A string kept out of the global cache.
+    Owned(sync::Arc<str>),
+}
+
+impl MaybeEstr {
+    /// The length in bytes of the longest string that [`MaybeEstr::new`] will
+    /// add to the cache.
+    pub const DEFAULT_MAX_LEN: usize = 32;
+
+    /// Create a `MaybeEstr`, interning the string if it is at most
+    /// [`MaybeEstr::DEFAULT_MAX_LEN`] bytes long or is already interned.
+    pub fn new(string: &str) -> MaybeEstr {
+        MaybeEstr::with_max_len(string, MaybeEstr::DEFAULT_MAX_LEN)
+    }
+
+    /// Create a `MaybeEstr`, interning the string if it is at most `max_len`
+    /// bytes long or is already interned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::MaybeEstr;
+    ///
+    /// assert!(!MaybeEstr::with_max_len("quick", 4).is_interned());
+    /// assert!(MaybeEstr::with_max_len("fox", 4).is_interned());
+    /// // Nothing new is interned with a limit of zero.
+    /// assert!(!MaybeEstr::with_max_len("lazy", 0).is_interned());
+    /// assert!(MaybeEstr::with_max_len("fox", 0).is_interned());
+    /// ```
+    pub fn with_max_len(string: &str, max_len: usize) -> MaybeEstr {
+        let interned = if string.len() <= max_len {
+            Estr::try_from_str(string).ok()
+        } else {
+            Estr::from_existing(string)
+        };
+        match interned {
+            Some(e) => MaybeEstr::Interned(e),
+            None => MaybeEstr::Owned(sync::Arc::from(string)),
+        }
+    }
+
+    /// Get the string as a `str`.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self {
+            MaybeEstr::Interned(e) => e.as_str(),
+            MaybeEstr::Owned(s) => s,
+        }
+    }
+
+    /// Returns `true` if the string is in the global cache.
+    #[inline]
+    pub fn is_interned(&self) -> bool {
+        matches!(self, MaybeEstr::Interned(_))
+    }
+
+    /// Get the interned `Estr`, if the string is in the global cache.
+    #[inline]
+    pub fn as_estr(&self) -> Option<Estr> {
+        match self {
+            MaybeEstr::Interned(e) => Some(*e),
+            MaybeEstr::Owned(_) => None,
+        }
+    }
+
+    /// Get the hash of the string. This is precomputed for interned strings,
+    /// and computed on each call for owned ones.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        match self {
+            MaybeEstr::Interned(e) => e.digest(),
+            MaybeEstr::Owned(s) => digest(s),
+        }
+    }
+}
+
+impl Default for MaybeEstr {
+    fn default() -> Self {
+        MaybeEstr::Interned(Estr::default())
+    }
+}
+
+impl PartialEq for MaybeEstr {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MaybeEstr::Interned(a), MaybeEstr::Interned(b)) => a == b,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for MaybeEstr {}
+
+impl PartialOrd for MaybeEstr {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Order the same way as `Estr`: by hash, then by contents.
+impl Ord for MaybeEstr {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.digest()
+            .cmp(&other.digest())
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+// Hash the same way as `Estr`, so that a `MaybeEstr` can key an identity
+// hashed map.
+impl hash::Hash for MaybeEstr {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.digest().hash.hash(state);
+    }
+}
+
+impl PartialEq<Estr> for MaybeEstr {
+    fn eq(&self, other: &Estr) -> bool {
+        match self {
+            MaybeEstr::Interned(e) => e == other,
+            MaybeEstr::Owned(s) => **s == *other.as_str(),
+        }
+    }
+}
+
+impl PartialEq<MaybeEstr> for Estr {
+    fn eq(&self, other: &MaybeEstr) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<str> for MaybeEstr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for MaybeEstr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<Estr> for MaybeEstr {
+    fn from(e: Estr) -> MaybeEstr {
+        MaybeEstr::Interned(e)
+    }
+}
+
+impl From<&str> for MaybeEstr {
+    fn from(s: &str) -> MaybeEstr {
+        MaybeEstr::new(s)
+    }
+}
+
+impl From<string::String> for MaybeEstr {
+    fn from(s: string::String) -> MaybeEstr {
+        MaybeEstr::new(&s)
+    }
+}
+
+impl From<MaybeEstr> for sync::Arc<str> {
+    fn from(m: MaybeEstr) -> sync::Arc<str> {
+        match m {
+            MaybeEstr::Interned(e) => sync::Arc::from(e.as_str()),
+            MaybeEstr::Owned(s) => s,
+        }
+    }
+}
+
+impl AsRef<str> for MaybeEstr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl ops::Deref for MaybeEstr {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for MaybeEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for MaybeEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
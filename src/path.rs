@@ -0,0 +1,267 @@
+use core::{fmt, ops};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Digest, EBytes, Estr};
+
+/// A handle representing an OS string in the global cache.
+///
+/// The encoded bytes of the OS string are interned like an [`EBytes`], so an
+/// `EOsStr` is a single pointer with O(1) equality and a precomputed hash.
+///
+/// # Examples
+///
+/// ```
+/// use std::ffi::OsStr;
+/// use estr::{EOsStr, estr};
+///
+/// let name = EOsStr::new("Cargo.toml");
+/// assert_eq!(name, EOsStr::new(OsStr::new("Cargo.toml")));
+/// assert_eq!(name.as_os_str(), "Cargo.toml");
+/// assert_eq!(name.to_estr(), Some(estr("Cargo.toml")));
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct EOsStr(EBytes);
+
+impl EOsStr {
+    /// Create a new `EOsStr` from the given OS string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache runs out of memory or goes over its budget.
+    pub fn new<S: AsRef<OsStr> + ?Sized>(string: &S) -> EOsStr {
+        EOsStr(EBytes::new(string.as_ref().as_encoded_bytes()))
+    }
+
+    /// Get the interned OS string.
+    #[inline]
+    pub fn as_os_str(&self) -> &'static OsStr {
+        // SAFETY: an `EOsStr` is only made from the encoded bytes of an
+        // `OsStr` in this process.
+        unsafe { OsStr::from_encoded_bytes_unchecked(self.0.as_bytes()) }
+    }
+
+    /// Get the encoded bytes of the OS string, as an `EBytes`.
+    #[inline]
+    pub fn as_ebytes(&self) -> EBytes {
+        self.0
+    }
+
+    /// Get an `Estr` with the same contents, if the OS string is valid
+    /// Unicode.
+    pub fn to_estr(&self) -> Option<Estr> {
+        self.0.to_estr()
+    }
+
+    /// Get the precomputed hash of the encoded bytes.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        self.0.digest()
+    }
+}
+
+impl PartialEq<OsStr> for EOsStr {
+    fn eq(&self, other: &OsStr) -> bool {
+        self.as_os_str() == other
+    }
+}
+
+impl PartialEq<&OsStr> for EOsStr {
+    fn eq(&self, other: &&OsStr) -> bool {
+        self.as_os_str() == *other
+    }
+}
+
+impl From<&OsStr> for EOsStr {
+    fn from(s: &OsStr) -> EOsStr {
+        EOsStr::new(s)
+    }
+}
+
+impl From<Estr> for EOsStr {
+    fn from(e: Estr) -> EOsStr {
+        EOsStr::new(e.as_str())
+    }
+}
+
+impl AsRef<OsStr> for EOsStr {
+    fn as_ref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl AsRef<Path> for EOsStr {
+    fn as_ref(&self) -> &Path {
+        Path::new(self.as_os_str())
+    }
+}
+
+impl ops::Deref for EOsStr {
+    type Target = OsStr;
+    fn deref(&self) -> &Self::Target {
+        self.as_os_str()
+    }
+}
+
+impl fmt::Debug for EOsStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_os_str(), f)
+    }
+}
+
+/// A handle representing a path in the global cache.
+///
+/// Like [`EOsStr`], this is a single pointer with O(1) equality, which makes
+/// it cheap to compare and hash large numbers of paths. Paths are interned
+/// exactly as given by [`EPath::new`], so `a/b` and `a//b` are different
+/// handles unless they are created with [`EPath::normalized`].
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use estr::{EOsStr, EPath, EPathMap};
+///
+/// let manifest = EPath::new("crates/estr/Cargo.toml");
+/// assert_eq!(manifest.parent(), Some(EPath::new("crates/estr")));
+/// assert_eq!(manifest.file_name(), Some(EOsStr::new("Cargo.toml")));
+/// assert_eq!(manifest.as_path(), Path::new("crates/estr/Cargo.toml"));
+///
+/// let mut sizes = EPathMap::default();
+/// sizes.insert(manifest, 512);
+/// assert_eq!(sizes.get(&EPath::normalized("./crates//estr/Cargo.toml")), Some(&512));
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct EPath(EOsStr);
+
+impl EPath {
+    /// Create a new `EPath` from the given path, exactly as written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache runs out of memory or goes over its budget.
+    pub fn new<P: AsRef<Path> + ?Sized>(path: &P) -> EPath {
+        EPath(EOsStr::new(path.as_ref()))
+    }
+
+    /// Create a new `EPath` from the given path after normalizing it
+    /// lexically.
+    ///
+    /// Repeated separators, `.` components and trailing separators are
+    /// removed. `..` components are kept, since removing them could change
+    /// which file the path refers to when symbolic links are involved. The
+    /// file system is not accessed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::EPath;
+    ///
+    /// assert_eq!(EPath::normalized("./src//lib.rs/"), EPath::new("src/lib.rs"));
+    /// assert_eq!(EPath::normalized("src/./../lib.rs"), EPath::new("src/../lib.rs"));
+    /// assert_eq!(EPath::normalized("./"), EPath::new("."));
+    /// ```
+    pub fn normalized<P: AsRef<Path> + ?Sized>(path: &P) -> EPath {
+        let path = path.as_ref();
+        let mut normal: PathBuf = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        if normal.as_os_str().is_empty() && !path.as_os_str().is_empty() {
+            normal.push(Component::CurDir);
+        }
+        EPath::new(&normal)
+    }
+
+    /// Get the interned path.
+    #[inline]
+    pub fn as_path(&self) -> &'static Path {
+        Path::new(self.0.as_os_str())
+    }
+
+    /// Get the interned path as an OS string.
+    #[inline]
+    pub fn as_os_str(&self) -> EOsStr {
+        self.0
+    }
+
+    /// Get the path without its final component, interned, if there is one.
+    /// See [`Path::parent`].
+    pub fn parent(&self) -> Option<EPath> {
+        self.as_path().parent().map(EPath::new)
+    }
+
+    /// Get the final component of the path, interned, if there is one. See
+    /// [`Path::file_name`].
+    pub fn file_name(&self) -> Option<EOsStr> {
+        self.as_path().file_name().map(EOsStr::new)
+    }
+
+    /// Get an `Estr` with the same contents, if the path is valid Unicode.
+    pub fn to_estr(&self) -> Option<Estr> {
+        self.0.to_estr()
+    }
+
+    /// Get the precomputed hash of the path.
+    #[inline]
+    pub fn digest(&self) -> Digest {
+        self.0.digest()
+    }
+}
+
+impl PartialEq<Path> for EPath {
+    fn eq(&self, other: &Path) -> bool {
+        self.as_path() == other
+    }
+}
+
+impl PartialEq<&Path> for EPath {
+    fn eq(&self, other: &&Path) -> bool {
+        self.as_path() == *other
+    }
+}
+
+impl From<&Path> for EPath {
+    fn from(p: &Path) -> EPath {
+        EPath::new(p)
+    }
+}
+
+impl From<Estr> for EPath {
+    fn from(e: Estr) -> EPath {
+        EPath::new(e.as_str())
+    }
+}
+
+impl From<EPath> for PathBuf {
+    fn from(p: EPath) -> PathBuf {
+        p.as_path().to_path_buf()
+    }
+}
+
+impl AsRef<Path> for EPath {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<OsStr> for EPath {
+    fn as_ref(&self) -> &OsStr {
+        self.as_path().as_os_str()
+    }
+}
+
+impl ops::Deref for EPath {
+    type Target = Path;
+    fn deref(&self) -> &Self::Target {
+        self.as_path()
+    }
+}
+
+impl fmt::Debug for EPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_path(), f)
+    }
+}
@@ -0,0 +1,111 @@
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::{Estr, LexEstr};
+
+/// An `Estr` serializes as a plain string.
+///
+/// Maps and sets keyed by `Estr` round-trip as well, since their keys
+/// serialize the same way.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{EstrMap, estr};
+///
+/// let mut map = EstrMap::default();
+/// map.insert(estr("fox"), 1);
+///
+/// let json = serde_json::to_string(&map).unwrap();
+/// assert_eq!(json, r#"{"fox":1}"#);
+/// assert_eq!(serde_json::from_str::<EstrMap<i32>>(&json).unwrap(), map);
+/// ```
+impl Serialize for Estr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Deserializing an `Estr` interns the string. When the format can lend us the
+/// string directly, this does not allocate anything outside the cache.
+///
+/// If the cache runs out of memory or is over its
+/// [budget](crate::CacheConfig::max_entries), deserialization fails with an
+/// error instead of panicking, so untrusted input cannot bring the program
+/// down.
+///
+/// ```
+/// use estr::{CacheConfig, Estr, configure};
+///
+/// configure(CacheConfig {
+///     max_entries: 1,
+///     ..CacheConfig::DEFAULT
+/// })
+/// .unwrap();
+///
+/// assert!(serde_json::from_str::<Estr>(r#""fox""#).is_ok());
+/// assert!(serde_json::from_str::<Estr>(r#""dog""#).is_err());
+/// assert!("dog".parse::<Estr>().is_err());
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use estr::{Estr, EstrSet, estr};
+///
+/// let fox: Estr = serde_json::from_str(r#""the quick brown fox""#).unwrap();
+/// assert_eq!(fox, estr("the quick brown fox"));
+///
+/// let set: EstrSet = [estr("quick"), estr("brown")].into_iter().collect();
+/// let bytes = postcard::to_allocvec(&set).unwrap();
+/// assert_eq!(postcard::from_bytes::<EstrSet>(&bytes).unwrap(), set);
+/// ```
+impl<'de> Deserialize<'de> for Estr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(EstrVisitor)
+    }
+}
+
+struct EstrVisitor;
+
+impl<'de> Visitor<'de> for EstrVisitor {
+    type Value = Estr;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Estr::try_from_str(v).map_err(E::custom)
+    }
+}
+
+/// A `LexEstr` serializes exactly like the `Estr` it wraps.
+impl Serialize for LexEstr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LexEstr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Estr::deserialize(deserializer).map(LexEstr)
+    }
+}
@@ -0,0 +1,279 @@
+use core::{cmp, fmt, hash, mem, ops, ptr, slice, str};
+
+use crate::Estr;
+
+/// A handle that keeps short strings inline and interns the rest.
+///
+/// Creating an [`Estr`] always hashes the string and looks it up in the global
+/// cache, which is wasted work for tiny strings that would fit in the handle
+/// itself. A `SmallEstr` is the same size as an `Estr`, but stores strings of
+/// up to [`SmallEstr::INLINE_CAP`] bytes (7 on 64-bit targets) directly in the
+/// handle, without touching the cache at all. Longer strings are interned as
+/// usual.
+///
+/// Each string has exactly one representation, so equality is a single
+/// comparison either way.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{Estr, SmallEstr, existing_estr};
+///
+/// assert_eq!(size_of::<SmallEstr>(), size_of::<Estr>());
+///
+/// let fox = SmallEstr::new("fox");
+/// assert!(fox.is_inline());
+/// assert_eq!(fox, "fox");
+/// assert_eq!(existing_estr("fox"), None);
+///
+/// let long = SmallEstr::new("the quick brown fox");
+/// assert!(!long.is_inline());
+/// assert_eq!(long.as_estr(), existing_estr("the quick brown fox"));
+/// ```
+#[derive(Copy, Clone)]
+#[repr(transparent)]
+pub struct SmallEstr {
+    // Either the pointer of an `Estr`, which is aligned and so has its low bit
+    // clear, or an inline string with no provenance. An inline string keeps
+    // `(len << 1) | 1` in its least significant byte, and its characters in
+    // the remaining bytes, in memory order.
+    repr: ptr::NonNull<u8>,
+}
+
+// The string is immutable, and interned strings live for the whole program.
+unsafe impl Send for SmallEstr {}
+unsafe impl Sync for SmallEstr {}
+
+const WIDTH: usize = mem::size_of::<usize>();
+
+// Where the tag byte and the characters of an inline string sit within the
+// bytes of `repr`.
+#[cfg(target_endian = "little")]
+const TAG_BYTE: usize = 0;
+#[cfg(target_endian = "little")]
+const CHARS_START: usize = 1;
+#[cfg(target_endian = "big")]
+const TAG_BYTE: usize = WIDTH - 1;
+#[cfg(target_endian = "big")]
+const CHARS_START: usize = 0;
+
+impl SmallEstr {
+    /// The length in bytes of the longest string kept inline.
+    pub const INLINE_CAP: usize = WIDTH - 1;
+
+    /// Create a `SmallEstr`, keeping the string inline if it is short enough
+    /// and interning it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the string has to be interned and the cache runs out of
+    /// memory.
+    pub fn new(string: &str) -> SmallEstr {
+        SmallEstr::inline(string).unwrap_or_else(|| SmallEstr::from_interned(Estr::from(string)))
+    }
+
+    /// Create a `SmallEstr` without touching the cache, if the string is
+    /// short enough to be kept inline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use estr::SmallEstr;
+    ///
+    /// const OK: Option<SmallEstr> = SmallEstr::inline("ok");
+    /// assert_eq!(OK, Some(SmallEstr::new("ok")));
+    /// assert_eq!(SmallEstr::inline("the quick brown fox"), None);
+    /// ```
+    pub const fn inline(string: &str) -> Option<SmallEstr> {
+        let bytes = string.as_bytes();
+        if bytes.len() > SmallEstr::INLINE_CAP {
+            return None;
+        }
+        let mut buf = [0u8; WIDTH];
+        buf[TAG_BYTE] = ((bytes.len() as u8) << 1) | 1;
+        let mut i = 0;
+        while i < bytes.len() {
+            buf[CHARS_START + i] = bytes[i];
+            i += 1;
+        }
+        // The tag bit is set, so the address is never zero.
+        let addr = usize::from_ne_bytes(buf);
+        Some(SmallEstr {
+            repr: unsafe { ptr::NonNull::new_unchecked(ptr::without_provenance_mut(addr)) },
+        })
+    }
+
+    // Wrap an interned string, which must be too long to be kept inline.
+    fn from_interned(e: Estr) -> SmallEstr {
+        debug_assert!(e.len() > SmallEstr::INLINE_CAP);
+        SmallEstr { repr: e.char_ptr }
+    }
+
+    /// Returns `true` if the string is kept inline rather than interned.
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        self.repr.as_ptr().addr() & 1 == 1
+    }
+
+    /// Get the interned `Estr`, if the string was too long to keep inline.
+    #[inline]
+    pub fn as_estr(&self) -> Option<Estr> {
+        (!self.is_inline()).then_some(Estr {
+            char_ptr: self.repr,
+        })
+    }
+
+    /// Get the string as an `Estr`, interning it if it is kept inline.
+    pub fn to_estr(&self) -> Estr {
+        self.as_estr().unwrap_or_else(|| Estr::from(self.as_str()))
+    }
+
+    /// Get the string as a `str`.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self.as_estr() {
+            Some(e) => e.as_str(),
+            None => {
+                let len = self.len();
+                // SAFETY: an inline `SmallEstr` is made from a `str` of `len`
+                // bytes copied to `CHARS_START` within `repr`.
+                unsafe {
+                    let chars = (&raw const self.repr).cast::<u8>().add(CHARS_START);
+                    str::from_utf8_unchecked(slice::from_raw_parts(chars, len))
+                }
+            }
+        }
+    }
+
+    /// Get the length (in bytes) of this string.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.as_estr() {
+            Some(e) => e.len(),
+            None => (self.repr.as_ptr().addr().to_ne_bytes()[TAG_BYTE] >> 1) as usize,
+        }
+    }
+
+    /// Returns `true` if this is the empty string.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for SmallEstr {
+    fn default() -> Self {
+        SmallEstr::new("")
+    }
+}
+
+impl PartialEq for SmallEstr {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_estr(), other.as_estr()) {
+            (Some(a), Some(b)) => a == b,
+            // Inline strings are equal exactly when their bits are.
+            _ => self.repr.as_ptr().addr() == other.repr.as_ptr().addr(),
+        }
+    }
+}
+
+impl Eq for SmallEstr {}
+
+impl PartialOrd for SmallEstr {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallEstr {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+// Interned strings use their precomputed hash. Inline strings mix their bits,
+// so that a `SmallEstr` can key a map with an identity hasher.
+impl hash::Hash for SmallEstr {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        match self.as_estr() {
+            Some(e) => e.hash(state),
+            None => {
+                let bits = self.repr.as_ptr().addr() as u64;
+                (bits ^ (bits >> 29))
+                    .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                    .hash(state);
+            }
+        }
+    }
+}
+
+impl PartialEq<str> for SmallEstr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SmallEstr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<Estr> for SmallEstr {
+    fn eq(&self, other: &Estr) -> bool {
+        match self.as_estr() {
+            Some(e) => e == *other,
+            None => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl PartialEq<SmallEstr> for Estr {
+    fn eq(&self, other: &SmallEstr) -> bool {
+        other == self
+    }
+}
+
+impl From<&str> for SmallEstr {
+    fn from(s: &str) -> SmallEstr {
+        SmallEstr::new(s)
+    }
+}
+
+impl From<Estr> for SmallEstr {
+    fn from(e: Estr) -> SmallEstr {
+        SmallEstr::inline(e.as_str()).unwrap_or_else(|| SmallEstr::from_interned(e))
+    }
+}
+
+impl From<SmallEstr> for Estr {
+    fn from(s: SmallEstr) -> Estr {
+        s.to_estr()
+    }
+}
+
+impl AsRef<str> for SmallEstr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl ops::Deref for SmallEstr {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for SmallEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for SmallEstr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
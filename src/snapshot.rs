@@ -0,0 +1,247 @@
+//! Saving the global string cache and loading it back.
+//!
+//! Programs that restart often tend to intern the same strings on every run.
+//! A snapshot records every string in the global cache together with its hash,
+//! so that a later run can load them all at once, either copying them into
+//! the cache with [`load`] or using them in place with [`attach`].
+//!
+//! A snapshot is a short header followed by the entries, each laid out exactly
+//! as in the cache: the hash and length as native-endian words, the bytes of
+//! the string, a null terminator, and zero padding up to the alignment of the
+//! next entry. Snapshots are therefore only portable between targets with the
+//! same word size and byte order, and between versions of this crate that
+//! hash strings the same way. [`load`] checks all of this and rejects any
+//! snapshot it cannot use.
+//!
+//! # Examples
+//!
+//! ```
+//! use estr::{estr, existing_estr, snapshot};
+//!
+//! estr("the quick brown fox");
+//! estr("jumps over the lazy dog");
+//! let bytes = snapshot::to_vec();
+//!
+//! // Then, in a later run of the program:
+//! let loaded = snapshot::load(&bytes).unwrap();
+//! assert!(loaded >= 2);
+//! assert!(existing_estr("jumps over the lazy dog").is_some());
+//! ```
+
+use alloc::{str, vec};
+use core::{convert, mem};
+
+use crate::error::SnapshotError;
+use crate::stringcache::StringCacheEntry;
+use crate::{USAGE, cache_iter, cfg, digest, insert_grouped, insert_hashed};
+
+const MAGIC: &[u8; 8] = b"ESTRSNAP";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 24;
+
+// Entries are laid out like `StringCacheEntry`, whose hash comes first.
+const ENTRY_LEN: usize = mem::size_of::<StringCacheEntry>();
+const ENTRY_ALIGN: usize = mem::align_of::<StringCacheEntry>();
+const LEN_OFFSET: usize = mem::size_of::<u64>();
+const WORD: usize = mem::size_of::<usize>();
+
+// A string whose hash is stored in the header, so that we notice if the hash
+// function has changed since the snapshot was written.
+const CANARY: &str = "estr snapshot";
+
+// The header: the magic, the version, the word size, the byte order and the
+// entry alignment, three zero bytes, and the hash of the canary.
+fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..10].copy_from_slice(&VERSION.to_le_bytes());
+    header[10] = WORD as u8;
+    header[11] = cfg!(target_endian = "big") as u8;
+    header[12] = ENTRY_ALIGN as u8;
+    header[16..].copy_from_slice(&digest(CANARY).hash.to_ne_bytes());
+    header
+}
+
+// Feed the snapshot to `put` piece by piece, returning the number of strings.
+fn encode<E>(mut put: impl FnMut(&[u8]) -> Result<(), E>) -> Result<usize, E> {
+    const PADDING: [u8; ENTRY_ALIGN] = [0; ENTRY_ALIGN];
+    put(&header())?;
+    let mut count = 0;
+    for e in cache_iter() {
+        let mut entry = [0u8; ENTRY_LEN];
+        entry[..LEN_OFFSET].copy_from_slice(&e.digest().hash.to_ne_bytes());
+        entry[LEN_OFFSET..LEN_OFFSET + WORD].copy_from_slice(&e.len().to_ne_bytes());
+        put(&entry)?;
+        put(e.as_str().as_bytes())?;
+        // The null terminator and the padding after it.
+        let size = ENTRY_LEN + e.len() + 1;
+        put(&PADDING[..size.next_multiple_of(ENTRY_ALIGN) - size + 1])?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Write a snapshot of every string in the global cache to a `Vec`.
+///
+/// Like [`cache_iter`], this sees the cache as it was when called.
+pub fn to_vec() -> vec::Vec<u8> {
+    let mut out = vec::Vec::new();
+    let Ok(_) = encode::<convert::Infallible>(|bytes| {
+        out.extend_from_slice(bytes);
+        Ok(())
+    });
+    out
+}
+
+cfg::std! {
+    /// Write a snapshot of every string in the global cache, returning the
+    /// number of strings written.
+    ///
+    /// The snapshot is written in many small pieces, so `out` should be
+    /// buffered. See [`to_vec`] for what is included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::BufWriter;
+    /// use estr::{estr, snapshot};
+    ///
+    /// estr("the quick brown fox");
+    /// let mut out = BufWriter::new(Vec::new());
+    /// let count = snapshot::write(&mut out).unwrap();
+    /// assert!(count >= 1);
+    /// assert_eq!(out.into_inner().unwrap(), snapshot::to_vec());
+    /// ```
+    pub fn write<W: std::io::Write>(out: &mut W) -> std::io::Result<usize> {
+        encode(|bytes| out.write_all(bytes))
+    }
+}
+
+/// Add every string in a snapshot to the global cache, returning the number
+/// of strings in the snapshot.
+///
+/// Each string is checked to be valid UTF-8 and to match the hash stored with
+/// it. The strings are added bin by bin, taking each lock once. Strings that
+/// are already interned are left as they are.
+///
+/// # Errors
+///
+/// Returns an error, without loading anything, if `bytes` is not a snapshot
+/// this build can use or any entry in it is malformed. Returns
+/// [`SnapshotError::Intern`] if the cache runs out of memory or goes over its
+/// budget part way through.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{SnapshotError, estr, snapshot};
+///
+/// estr("the quick brown fox");
+/// let mut bytes = snapshot::to_vec();
+///
+/// assert_eq!(snapshot::load(b"the quick brown fox"), Err(SnapshotError::NotASnapshot));
+/// bytes.truncate(bytes.len() - 1);
+/// assert!(matches!(snapshot::load(&bytes), Err(SnapshotError::Corrupt { .. })));
+///
+/// // Flip a bit in the hash of the first entry.
+/// let mut bytes = snapshot::to_vec();
+/// bytes[24] ^= 1;
+/// assert_eq!(snapshot::load(&bytes), Err(SnapshotError::Corrupt { offset: 24 }));
+/// ```
+pub fn load(bytes: &[u8]) -> Result<usize, SnapshotError> {
+    let strings = decode(bytes)?;
+    insert_hashed(&strings)?;
+    Ok(strings.len())
+}
+
+/// Add every string in a snapshot to the global cache without copying it,
+/// returning the number of strings in the snapshot.
+///
+/// This is meant for a snapshot mapped into memory, for example with the
+/// `memmap2` crate, so that processes loading the same file share one copy of
+/// it. Strings that are not interned yet become `Estr` handles pointing into
+/// `bytes`, which must therefore live, and stay unchanged, for the rest of the
+/// program. Strings that are already interned are left as they are.
+///
+/// Attached strings count against the entry budget of the cache, but not its
+/// byte budget. Otherwise they behave like any other interned string: they
+/// are visited by [`cache_iter`], and so included in new snapshots.
+///
+/// # Errors
+///
+/// As for [`load`], and also returns [`SnapshotError::Misaligned`] if `bytes`
+/// does not start at a multiple of 8 bytes. Memory maps always start at the
+/// beginning of a page, so this only happens with other sources of memory.
+///
+/// # Examples
+///
+/// ```
+/// use estr::{SnapshotError, estr, existing_estr, snapshot};
+///
+/// estr("the quick brown fox");
+/// let bytes = snapshot::to_vec();
+///
+/// // A memory map of a snapshot file would do here. Instead, copy the
+/// // snapshot to an aligned buffer that lives for the rest of the program.
+/// let words = vec![0u64; bytes.len().div_ceil(8)].leak();
+/// let region = unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr().cast(), bytes.len()) };
+/// region.copy_from_slice(&bytes);
+/// let region: &'static [u8] = region;
+///
+/// assert!(snapshot::attach(region).unwrap() >= 1);
+/// assert!(existing_estr("the quick brown fox").is_some());
+/// assert!(estr::cache_iter().any(|e| e == "the quick brown fox"));
+/// assert_eq!(snapshot::attach(&region[1..]), Err(SnapshotError::Misaligned));
+/// ```
+pub fn attach(bytes: &'static [u8]) -> Result<usize, SnapshotError> {
+    if !bytes.as_ptr().cast::<StringCacheEntry>().is_aligned() {
+        return Err(SnapshotError::Misaligned);
+    }
+    let strings = decode(bytes)?;
+    insert_grouped(&strings, |sc, _, string| {
+        // SAFETY: `decode` checked that each string follows an entry header
+        // and ends with a null terminator, and the header is aligned since
+        // the snapshot and each entry in it are.
+        let entry = unsafe { string.as_ptr().cast::<StringCacheEntry>().sub(1) };
+        sc.insert_entry(entry, &USAGE)
+    })?;
+    Ok(strings.len())
+}
+
+// Check a snapshot and find the strings in it, along with their hashes.
+fn decode(bytes: &[u8]) -> Result<vec::Vec<(u64, &str)>, SnapshotError> {
+    if bytes.len() < HEADER_LEN || bytes[..8] != *MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    if bytes[..HEADER_LEN] != header() {
+        return Err(SnapshotError::Incompatible);
+    }
+
+    let mut strings = vec::Vec::new();
+    let mut offset = HEADER_LEN;
+    while offset < bytes.len() {
+        let corrupt = SnapshotError::Corrupt { offset };
+        let entry = bytes.get(offset..offset + ENTRY_LEN).ok_or(corrupt)?;
+        let hash = u64::from_ne_bytes(entry[..LEN_OFFSET].try_into().unwrap());
+        let len = usize::from_ne_bytes(entry[LEN_OFFSET..LEN_OFFSET + WORD].try_into().unwrap());
+
+        // The string, its null terminator, and the padding after it.
+        let start = offset + ENTRY_LEN;
+        let end = start.checked_add(len).ok_or(corrupt)?;
+        let next = end
+            .checked_add(1)
+            .and_then(|n| n.checked_next_multiple_of(ENTRY_ALIGN))
+            .ok_or(corrupt)?;
+        if next > bytes.len() || bytes[end] != 0 {
+            return Err(corrupt);
+        }
+        let string = str::from_utf8(&bytes[start..end]).map_err(|_| corrupt)?;
+        // A wrong hash would let the string into the cache a second time.
+        if digest(string).hash != hash {
+            return Err(corrupt);
+        }
+        strings.push((hash, string));
+        offset = next;
+    }
+    Ok(strings)
+}
@@ -1,9 +1,14 @@
-use alloc::{slice, vec};
-use core::{mem, primitive::str, ptr};
+use alloc::{alloc::GlobalAlloc, boxed, slice, vec};
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use core::{iter, mem, ptr};
 
+use crate::Estr;
 use crate::bumpalloc::LeakyBumpAlloc;
+use crate::config::{CacheConfig, config};
+use crate::error::InternError;
+use crate::platform::Mutex;
 
-// `StringCache` stores a `Vec` of pointers to the `StringCacheEntry` structs.
+// `StringCache` stores a table of pointers to the `StringCacheEntry` structs.
 // The actual memory for the `StringCacheEntry` is stored in the LeakyBumpAlloc,
 // and each `Alloc` is rotated out when it's full and a new one twice its size
 // is allocated. The Allocator memory is never freed so our strings essentialy
@@ -23,69 +28,149 @@ use crate::bumpalloc::LeakyBumpAlloc;
 // Proper alignment is guaranteed when allocating each entry as the alignment
 // is baked into the allocator. `StringCache` is responsible for monitoring the
 // Allocator and creating a new one when it would overflow -- the `Alloc` itself
-// will just `abort()` if it runs out of memory. Note that we abort() rather
-// than panic because the behaviour of the spinlock in case of a panic while
-// holding the lock is undefined.
+// will just `abort()` if it runs out of memory. Any memory the insertion needs,
+// for a new allocator or a bigger table, is obtained before the cache is
+// modified, so running out of memory is reported as an error and leaves the
+// cache as it was.
 //
-// Thread safety is ensured because we can only access the `StringCache` through
-// the spinlock in the `lazy_static` ref. The initial capacity of the cache is
-// divided evenly among a number of 'bins' or shards each with their own lock,
-// in order to reduce contention.
+// Thread safety for writers is ensured because we can only modify the
+// `StringCache` through the lock in its `Bin`. The initial capacity of the
+// cache is divided evenly among a number of 'bins' or shards each with their
+// own lock, in order to reduce contention.
+//
+// Readers don't take the lock at all. Each slot of the table is an atomic
+// pointer which is only set (with release ordering) once the entry it points
+// to has been fully written, and entries are never modified afterwards. When
+// the table grows, the new table is filled in privately and then published
+// through the `Bin`. The old table is kept alive until the `StringCache` is
+// dropped, so a reader still probing it never sees freed memory; it may just
+// miss strings inserted after the switch. For the global cache this means old
+// tables are never freed, which costs at most as much memory as the current
+// table.
 #[repr(align(128))]
 pub(crate) struct StringCache {
     pub(crate) alloc: LeakyBumpAlloc,
+    arena: &'static (dyn GlobalAlloc + Sync),
     pub(crate) old_allocs: vec::Vec<LeakyBumpAlloc>,
-    entries: vec::Vec<*mut StringCacheEntry>,
+    table: boxed::Box<EntryTable>,
+    // These are boxed so that their addresses stay stable for readers.
+    #[allow(clippy::vec_box)]
+    old_tables: vec::Vec<boxed::Box<EntryTable>>,
+    // Entries that live outside of our allocators, such as those of an
+    // attached snapshot, so that they can still be iterated over.
+    external: vec::Vec<*const StringCacheEntry>,
     num_entries: usize,
-    mask: usize,
     total_allocated: usize,
+    // The budget of the whole cache, which is shared between bins through a
+    // `Usage`.
+    max_bytes: usize,
+    max_entries: usize,
     // Padding and aligning to 128 bytes gives up to 20% performance
     // improvement this actually aligns to 256 bytes because of the Mutex
     // around it.
     _pad: [u32; 3],
 }
 
-// Initial size of the StringCache table
+// Default initial size of the StringCache table
 pub(crate) const INITIAL_CAPACITY: usize = 1 << 20;
-// Initial size of the allocator storage (in bytes)
+// Default initial size of the allocator storage (in bytes)
 pub(crate) const INITIAL_ALLOC: usize = 4 << 20;
-// Number of bins (shards) for map
+// Maximum, and default, number of bins (shards) for map
 pub(crate) const BIN_SHIFT: usize = 6;
 pub(crate) const NUM_BINS: usize = 1 << BIN_SHIFT;
 // Shift for top bits to determine bin a hash falls into
 pub(crate) const TOP_SHIFT: usize = 8 * mem::size_of::<usize>() - BIN_SHIFT;
 
-impl StringCache {
-    /// Create a new StringCache with the given starting capacity
-    pub(crate) fn new() -> StringCache {
-        let capacity = INITIAL_CAPACITY / NUM_BINS;
-        let alloc = LeakyBumpAlloc::new(
-            INITIAL_ALLOC / NUM_BINS,
-            mem::align_of::<StringCacheEntry>(),
-        );
-        StringCache {
-            // Current allocator.
-            alloc,
-            // Old allocators we'll keep around for iteration purposes.
-            // 16 would mean we've allocated 128GB of string storage since we
-            // double each time.
-            old_allocs: vec::Vec::with_capacity(16),
-            // Vector of pointers to the `StringCacheEntry` headers.
-            entries: vec![ptr::null_mut(); capacity],
-            num_entries: 0,
-            mask: capacity - 1,
-            total_allocated: capacity,
-            _pad: [0u32; 3],
+// One shard of a string cache: the `StringCache` itself behind a lock, and the
+// currently published table of entries for lock-free readers.
+pub(crate) struct Bin {
+    table: AtomicPtr<EntryTable>,
+    cache: Mutex<Option<StringCache>>,
+}
+
+impl Bin {
+    pub(crate) const fn new() -> Bin {
+        Bin {
+            table: AtomicPtr::new(ptr::null_mut()),
+            cache: Mutex::new(None),
+        }
+    }
+
+    // Lock the bin and run `f` on its cache, creating the cache with `init` if
+    // this is the first use. Afterwards the table is published, in case `f`
+    // grew it.
+    pub(crate) fn with_cache<R>(
+        &self,
+        init: impl FnOnce() -> Result<StringCache, InternError>,
+        f: impl FnOnce(&mut StringCache) -> Result<R, InternError>,
+    ) -> Result<R, InternError> {
+        let mut sc = self.cache.lock();
+        let sc = match &mut *sc {
+            Some(sc) => sc,
+            None => sc.insert(init()?),
+        };
+        let result = f(sc);
+        let table = &*sc.table as *const EntryTable as *mut EntryTable;
+        if self.table.load(Ordering::Relaxed) != table {
+            self.table.store(table, Ordering::Release);
         }
+        result
     }
 
-    pub(crate) fn get_existing(&self, string: &str, hash: u64) -> Option<*const u8> {
+    // Lock the bin and run `f` on its cache, if it has been created.
+    pub(crate) fn with_existing_cache<R>(&self, f: impl FnOnce(&StringCache) -> R) -> Option<R> {
+        self.cache.lock().as_ref().map(f)
+    }
+
+    // Lock-free lookup of a string.
+    pub(crate) fn get_existing(&self, string: &[u8], hash: u64) -> Option<*const u8> {
+        self.published()?.find(string, hash).ok()
+    }
+
+    // Lock-free lookup of a string by its hash alone.
+    pub(crate) fn get_by_hash(&self, hash: u64) -> Option<*const u8> {
+        self.published()?.find_hash(hash)
+    }
+
+    fn published(&self) -> Option<&EntryTable> {
+        let table = self.table.load(Ordering::Acquire);
+        // The table was fully initialized before it was published, and is not
+        // freed until the `StringCache` that owns it is dropped, which needs
+        // exclusive access to this bin.
+        unsafe { table.as_ref() }
+    }
+}
+
+// An open-addressed hash table of pointers to entries, probed with triangular
+// numbers. The number of slots is always a power of two.
+struct EntryTable {
+    mask: usize,
+    slots: boxed::Box<[AtomicPtr<StringCacheEntry>]>,
+}
+
+impl EntryTable {
+    fn new(capacity: usize) -> Result<EntryTable, InternError> {
+        debug_assert!(capacity.is_power_of_two());
+        let mut slots = vec::Vec::new();
+        slots
+            .try_reserve_exact(capacity)
+            .map_err(|_| InternError::OutOfMemory)?;
+        slots.extend((0..capacity).map(|_| AtomicPtr::new(ptr::null_mut())));
+        Ok(EntryTable {
+            mask: capacity - 1,
+            slots: slots.into_boxed_slice(),
+        })
+    }
+
+    // Find the given string, returning a pointer to its chars if it's in the
+    // table, or else the position of the empty slot where it belongs.
+    fn find(&self, string: &[u8], hash: u64) -> Result<*const u8, usize> {
         let mut pos = self.mask & hash as usize;
         let mut dist = 0;
         loop {
-            let entry = unsafe { self.entries.get_unchecked(pos) };
+            let entry = unsafe { self.slots.get_unchecked(pos) }.load(Ordering::Acquire);
             if entry.is_null() {
-                return None;
+                return Err(pos);
             }
             // This is safe as long as entry points to a valid address and the
             // layout described in the `StringCache` doc comment holds.
@@ -93,20 +178,17 @@ impl StringCache {
                 // entry is a `*StringCacheEntry` so offseting by 1 gives us a
                 // pointer to the end of the entry, aka the beginning of the
                 // chars.
-                // As long as the memory is valid and the layout is correct,
-                // we're safe to create a string slice from the chars since
-                // they were copied directly from a valid `str`.
                 let entry_chars = entry.add(1) as *const u8;
-                // if entry is non-null then it must point to a valid
-                // StringCacheEntry
-                let sce = &**entry;
+                // If entry is non-null then it must point to a valid
+                // `StringCacheEntry`, which was fully written before the
+                // pointer was stored.
+                let sce = &*entry;
                 if sce.hash == hash
                     && sce.len == string.len()
-                    && str::from_utf8_unchecked(slice::from_raw_parts(entry_chars, sce.len))
-                        == string
+                    && slice::from_raw_parts(entry_chars, sce.len) == string
                 {
                     // found matching string in the cache already, return it
-                    return Some(entry_chars);
+                    return Ok(entry_chars);
                 }
             }
 
@@ -117,52 +199,104 @@ impl StringCache {
         }
     }
 
-    // Insert the given string with its given hash into the cache.
-    pub(crate) fn insert(&mut self, string: &str, hash: u64) -> *const u8 {
+    // Find a string by its hash alone. If more than one string in the table
+    // has this hash, the first one found along the probe sequence is returned.
+    fn find_hash(&self, hash: u64) -> Option<*const u8> {
         let mut pos = self.mask & hash as usize;
         let mut dist = 0;
         loop {
-            let entry = unsafe { self.entries.get_unchecked(pos) };
+            let entry = unsafe { self.slots.get_unchecked(pos) }.load(Ordering::Acquire);
             if entry.is_null() {
-                // found empty slot to insert
-                break;
+                return None;
             }
-
-            // This is safe as long as entry points to a valid address and the
-            // layout described in the `StringCache` doc comment holds.
+            // If entry is non-null then it must point to a valid
+            // `StringCacheEntry`, and the chars start right after it.
             unsafe {
-                // entry is a `*StringCacheEntry` so offseting by 1 gives us a
-                // pointer to the end of the entry, aka the beginning of the
-                // chars.
-                // As long as the memory is valid and the layout is correct,
-                // we're safe to create a string slice from the chars since
-                // they were copied directly from a valid `str`.
-                let entry_chars = entry.add(1) as *const u8;
-                // If entry is non-null then it must point to a valid
-                // `StringCacheEntry`.
-                let sce = &**entry;
-                if sce.hash == hash
-                    && sce.len == string.len()
-                    && str::from_utf8_unchecked(slice::from_raw_parts(entry_chars, sce.len))
-                        == string
-                {
-                    // found matching string in the cache already, return it
-                    return entry_chars;
+                if (*entry).hash == hash {
+                    return Some(entry.add(1) as *const u8);
                 }
             }
 
-            // keep looking
+            // Keep looking.
             dist += 1;
             debug_assert!(dist <= self.mask);
             pos = (pos + dist) & self.mask;
         }
+    }
+
+    // Iterate over the positions and entries of occupied slots.
+    fn entries(&self) -> impl Iterator<Item = (usize, *mut StringCacheEntry)> + '_ {
+        self.slots
+            .iter()
+            .map(|slot| slot.load(Ordering::Acquire))
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_null())
+    }
+}
+
+impl StringCache {
+    /// Create a new StringCache for one bin of the global cache.
+    pub(crate) fn new() -> Result<StringCache, InternError> {
+        StringCache::with_config(config())
+    }
+
+    /// Create a new StringCache for one bin of a cache with the given
+    /// configuration. The starting sizes are rounded up as needed.
+    pub(crate) fn with_config(config: &CacheConfig) -> Result<StringCache, InternError> {
+        let (capacity, alloc_size) = config.per_bin();
+        let arena = config.arena;
+        let align = mem::align_of::<StringCacheEntry>();
+        let capacity = capacity
+            .checked_next_power_of_two()
+            .ok_or(InternError::OutOfMemory)?
+            .max(2);
+        let alloc_size = alloc_size
+            .max(align)
+            .checked_next_multiple_of(align)
+            .ok_or(InternError::OutOfMemory)?;
+        let alloc =
+            LeakyBumpAlloc::new(alloc_size, align, arena).ok_or(InternError::OutOfMemory)?;
+        Ok(StringCache {
+            // Current allocator.
+            alloc,
+            // Where new allocators get their memory.
+            arena,
+            // Old allocators we'll keep around for iteration purposes.
+            // 16 would mean we've allocated 128GB of string storage since we
+            // double each time.
+            old_allocs: vec::Vec::with_capacity(16),
+            // Table of pointers to the `StringCacheEntry` headers.
+            table: boxed::Box::new(EntryTable::new(capacity)?),
+            // Old tables that lock-free readers may still be probing.
+            old_tables: vec::Vec::new(),
+            external: vec::Vec::new(),
+            num_entries: 0,
+            total_allocated: alloc_size,
+            max_bytes: config.max_bytes,
+            max_entries: config.max_entries,
+            _pad: [0u32; 3],
+        })
+    }
+
+    // Insert the given string with its given hash into the cache, counting it
+    // against the budget in `usage`.
+    pub(crate) fn insert(
+        &mut self,
+        string: &[u8],
+        hash: u64,
+        usage: &Usage,
+    ) -> Result<*const u8, InternError> {
+        let pos = match self.table.find(string, hash) {
+            // found matching string in the cache already, return it
+            Ok(entry_chars) => return Ok(entry_chars),
+            // found empty slot to insert
+            Err(pos) => pos,
+        };
 
         //
         // Insert the new string.
         //
 
-        // We know pos is in bounds as it's &ed with the mask above.
-        let entry_ptr = unsafe { self.entries.get_unchecked_mut(pos) };
         // Ddd one to length for null byte.
         // There's no way we could overflow here in practice since that would
         // require having allocated a `u64::MAX`-length string, by which time
@@ -171,26 +305,19 @@ impl StringCache {
         let byte_len = string.len() + 1;
         let alloc_size = mem::size_of::<StringCacheEntry>() + byte_len;
 
-        // if our new allocation would spill over the allocator, make a new
-        // allocator and let the old one leak
-        let capacity = self.alloc.capacity();
-        let allocated = self.alloc.allocated();
-        if alloc_size
-            .checked_add(allocated)
-            .expect("overflowed alloc_size + allocated")
-            > capacity
-        {
-            let new_capacity = capacity
-                .checked_mul(2)
-                .expect("capacity * 2 overflowed")
-                .max(alloc_size);
-            let old_alloc = mem::replace(
-                &mut self.alloc,
-                LeakyBumpAlloc::new(new_capacity, mem::align_of::<StringCacheEntry>()),
-            );
-            self.old_allocs.push(old_alloc);
-            self.total_allocated += new_capacity;
-        }
+        // Count the entry against the budget before allocating anything for
+        // it, including the padding the allocator will add.
+        let charged = alloc_size
+            .checked_next_multiple_of(mem::align_of::<StringCacheEntry>())
+            .ok_or(InternError::OutOfMemory)?;
+        usage.reserve(charged, self.max_bytes, self.max_entries)?;
+        let pos = match self.make_room(string, hash, pos, alloc_size) {
+            Ok(pos) => pos,
+            Err(err) => {
+                usage.release(charged, self.max_bytes, self.max_entries);
+                return Err(err);
+            }
+        };
 
         // This is safe as long as:
         // 1. `alloc_size` is calculated correctly.
@@ -199,13 +326,13 @@ impl StringCache {
         // 3. The `StringCacheEntry` layout descibed above holds and the memory
         //    returned by allocate() is prooperly aligned.
         unsafe {
-            *entry_ptr = self.alloc.allocate(alloc_size) as *mut StringCacheEntry;
+            let entry_ptr = self.alloc.allocate(alloc_size) as *mut StringCacheEntry;
 
             // Write the header.
             // `entry_ptr` is guaranteed to point to a valid `StringCacheEntry`,
             // or `alloc.allocate()` would have aborted.
             ptr::write(
-                *entry_ptr,
+                entry_ptr,
                 StringCacheEntry {
                     hash,
                     len: string.len(),
@@ -213,47 +340,199 @@ impl StringCache {
             );
             // Write the characters after the `StringCacheEntry`.
             let char_ptr = entry_ptr.add(1) as *mut u8;
-            ptr::copy_nonoverlapping(string.as_bytes().as_ptr(), char_ptr, string.len());
+            ptr::copy_nonoverlapping(string.as_ptr(), char_ptr, string.len());
             // Write the trailing null.
             let write_ptr = char_ptr.add(string.len());
             ptr::write(write_ptr, 0u8);
 
+            // Only now that the entry is complete may readers see it.
+            // We know pos is in bounds as it was returned by `find`.
+            self.table
+                .slots
+                .get_unchecked(pos)
+                .store(entry_ptr, Ordering::Release);
+
             self.num_entries += 1;
-            // We want to keep an 0.5 load factor for the map, so grow if we've
-            // exceeded that.
-            if self.num_entries * 2 > self.mask {
-                self.grow();
+
+            Ok(char_ptr)
+        }
+    }
+
+    // Add an entry that lives outside of our allocators, for example in a
+    // mapped snapshot, unless we already have the same string. The entry must
+    // stay valid and unchanged for as long as the cache lives.
+    pub(crate) fn insert_entry(
+        &mut self,
+        entry: *const StringCacheEntry,
+        usage: &Usage,
+    ) -> Result<*const u8, InternError> {
+        // SAFETY: the caller gives us a valid entry, followed by its
+        // characters and a null terminator.
+        let (string, hash) = unsafe {
+            let chars = entry.add(1) as *const u8;
+            (slice::from_raw_parts(chars, (*entry).len), (*entry).hash)
+        };
+        let pos = match self.table.find(string, hash) {
+            Ok(entry_chars) => return Ok(entry_chars),
+            Err(pos) => pos,
+        };
+
+        // The entry counts against the budget, but its bytes do not since
+        // they are not ours.
+        usage.reserve(0, self.max_bytes, self.max_entries)?;
+        let room = self
+            .external
+            .try_reserve(1)
+            .map_err(|_| InternError::OutOfMemory)
+            .and_then(|()| self.make_slot(string, hash, pos));
+        let pos = match room {
+            Ok(pos) => pos,
+            Err(err) => {
+                usage.release(0, self.max_bytes, self.max_entries);
+                return Err(err);
             }
+        };
+        // SAFETY: pos is in bounds as it was returned by `find`.
+        unsafe { self.table.slots.get_unchecked(pos) }
+            .store(entry as *mut StringCacheEntry, Ordering::Release);
+        self.external.push(entry);
+        self.num_entries += 1;
+        Ok(string.as_ptr())
+    }
 
-            char_ptr
+    // Make sure there is a free slot for a new entry, returning the slot to
+    // use in place of `pos`.
+    fn make_slot(
+        &mut self,
+        string: &[u8],
+        hash: u64,
+        mut pos: usize,
+    ) -> Result<usize, InternError> {
+        // We want to keep an 0.5 load factor for the map, so grow first if
+        // this entry would exceed that, and find the slot again in the new
+        // table.
+        if (self.num_entries + 1) * 2 > self.table.mask {
+            self.grow()?;
+            pos = self.table.find(string, hash).unwrap_err();
         }
+        Ok(pos)
+    }
+
+    // Make sure there is a free slot and enough allocator space for a new
+    // entry of `alloc_size` bytes, returning the slot to use.
+    fn make_room(
+        &mut self,
+        string: &[u8],
+        hash: u64,
+        pos: usize,
+        alloc_size: usize,
+    ) -> Result<usize, InternError> {
+        let pos = self.make_slot(string, hash, pos)?;
+
+        // if our new allocation would spill over the allocator, make a new
+        // allocator and let the old one leak
+        let capacity = self.alloc.capacity();
+        let allocated = self.alloc.allocated();
+        if alloc_size
+            .checked_add(allocated)
+            .ok_or(InternError::OutOfMemory)?
+            > capacity
+        {
+            // Keep the capacity a multiple of the alignment so that entries
+            // can be walked from the start of a chunk to its end.
+            let align = mem::align_of::<StringCacheEntry>();
+            let new_capacity = capacity
+                .checked_mul(2)
+                .zip(alloc_size.checked_next_multiple_of(align))
+                .map(|(doubled, needed)| doubled.max(needed))
+                .ok_or(InternError::OutOfMemory)?;
+            self.old_allocs
+                .try_reserve(1)
+                .map_err(|_| InternError::OutOfMemory)?;
+            let new_alloc = LeakyBumpAlloc::new(new_capacity, align, self.arena)
+                .ok_or(InternError::OutOfMemory)?;
+            let old_alloc = mem::replace(&mut self.alloc, new_alloc);
+            self.old_allocs.push(old_alloc);
+            self.total_allocated += new_capacity;
+        }
+
+        Ok(pos)
+    }
+
+    // Gather statistics about this bin by walking its table.
+    pub(crate) fn stats(&self) -> BinStats {
+        let mask = self.table.mask;
+        let mut stats = BinStats {
+            entries: self.num_entries,
+            table_capacity: mask + 1,
+            capacity_bytes: self.total_allocated,
+            ..BinStats::default()
+        };
+        for alloc in self.old_allocs.iter() {
+            stats.allocated_bytes += alloc.allocated();
+            stats.wasted_bytes += alloc.capacity() - alloc.allocated();
+        }
+        stats.allocated_bytes += self.alloc.allocated();
+
+        for (pos, entry) in self.table.entries() {
+            // If entry is non-null then it must point to a valid
+            // `StringCacheEntry`.
+            let sce = unsafe { &*entry };
+            stats.string_bytes += sce.len;
+
+            // Replay the probe sequence used by `insert` to find how far this
+            // entry landed from its ideal slot.
+            let mut probe = mask & sce.hash as usize;
+            let mut dist = 0;
+            while probe != pos {
+                dist += 1;
+                probe = (probe + dist) & mask;
+            }
+            stats.max_probe = stats.max_probe.max(dist);
+        }
+        stats
+    }
+
+    // The memory ranges holding entries, across the current and old allocators,
+    // and one range for each external entry.
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = (*const u8, *const u8)> + '_ {
+        let external = self.external.iter().map(|&entry| {
+            // SAFETY: external entries stay valid for as long as the cache.
+            let size = mem::size_of::<StringCacheEntry>() + unsafe { (*entry).len } + 1;
+            let start = entry as *const u8;
+            let end =
+                start.wrapping_add(size.next_multiple_of(mem::align_of::<StringCacheEntry>()));
+            (start, end)
+        });
+        self.old_allocs
+            .iter()
+            .chain(iter::once(&self.alloc))
+            .map(LeakyBumpAlloc::used)
+            .chain(external)
     }
 
     // Double the size of the map storage.
     //
-    // This is safe as long as:
-    // - The in-memory layout of the `StringCacheEntry` is correct.
-    //
-    // If there's not enough memory for the new entry table, it will just abort
-    pub(crate) unsafe fn grow(&mut self) {
-        let new_mask = self.mask * 2 + 1;
-
-        let mut new_entries: vec::Vec<*mut StringCacheEntry> = vec![ptr::null_mut(); new_mask + 1];
+    // If there's not enough memory for the new entry table, this returns an
+    // error and leaves the old table in place.
+    pub(crate) fn grow(&mut self) -> Result<(), InternError> {
+        let new_capacity = (self.table.mask + 1)
+            .checked_mul(2)
+            .ok_or(InternError::OutOfMemory)?;
+        let new_table = EntryTable::new(new_capacity)?;
+        let new_mask = new_table.mask;
 
         // copy the existing map into the new map
         let mut to_copy = self.num_entries;
-        for e in self.entries.iter_mut() {
-            if e.is_null() {
-                continue;
-            }
-
+        for (_, e) in self.table.entries() {
             // Start of the entry is the hash.
-            // SAFETY: TODO
-            let hash = unsafe { *(*e as *const u64) };
+            // SAFETY: non-null slots point to a valid `StringCacheEntry`,
+            // which starts with the hash.
+            let hash = unsafe { *(e as *const u64) };
             let mut pos = (hash as usize) & new_mask;
             let mut dist = 0;
             loop {
-                if new_entries[pos].is_null() {
+                if new_table.slots[pos].load(Ordering::Relaxed).is_null() {
                     // Here's an empty slot to put the pointer in.
                     break;
                 }
@@ -265,15 +544,77 @@ impl StringCache {
                 pos = pos.wrapping_add(dist) & new_mask;
             }
 
-            new_entries[pos] = *e;
+            // No reader can see the new table until it is published, which
+            // orders these stores before it.
+            new_table.slots[pos].store(e, Ordering::Relaxed);
             to_copy -= 1;
             if to_copy == 0 {
                 break;
             }
         }
 
-        self.entries = new_entries;
-        self.mask = new_mask;
+        // Make room to retire the old table before replacing it, so that
+        // failing here leaves everything as it was.
+        self.old_tables
+            .try_reserve(1)
+            .map_err(|_| InternError::OutOfMemory)?;
+        let old_table = mem::replace(&mut self.table, boxed::Box::new(new_table));
+        self.old_tables.push(old_table);
+        Ok(())
+    }
+}
+
+// How much of its budget a whole cache has used, shared by all of its bins.
+// Space is reserved before a bin allocates anything for a new entry, so
+// concurrent inserts into different bins can never overshoot the limits.
+pub(crate) struct Usage {
+    bytes: AtomicUsize,
+    entries: AtomicUsize,
+}
+
+impl Usage {
+    pub(crate) const fn new() -> Usage {
+        Usage {
+            bytes: AtomicUsize::new(0),
+            entries: AtomicUsize::new(0),
+        }
+    }
+
+    // Reserve one entry of `bytes` bytes, or fail if that would go over
+    // either limit.
+    pub(crate) fn reserve(
+        &self,
+        bytes: usize,
+        max_bytes: usize,
+        max_entries: usize,
+    ) -> Result<(), InternError> {
+        // Skip the bookkeeping entirely for an unlimited cache.
+        if max_bytes == usize::MAX && max_entries == usize::MAX {
+            return Ok(());
+        }
+        self.entries
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |entries| {
+                entries.checked_add(1).filter(|&n| n <= max_entries)
+            })
+            .map_err(|_| InternError::BudgetExceeded)?;
+        self.bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|&n| n <= max_bytes)
+            })
+            .map_err(|_| {
+                self.entries.fetch_sub(1, Ordering::Relaxed);
+                InternError::BudgetExceeded
+            })?;
+        Ok(())
+    }
+
+    // Give back a reservation for an entry that was not inserted after all.
+    pub(crate) fn release(&self, bytes: usize, max_bytes: usize, max_entries: usize) {
+        if max_bytes == usize::MAX && max_entries == usize::MAX {
+            return;
+        }
+        self.entries.fetch_sub(1, Ordering::Relaxed);
+        self.bytes.fetch_sub(bytes, Ordering::Relaxed);
     }
 }
 
@@ -286,3 +627,108 @@ pub(crate) struct StringCacheEntry {
     pub(crate) hash: u64,
     pub(crate) len: usize,
 }
+
+/// Memory usage of the global string cache, returned by
+/// [`cache_stats`](crate::cache_stats).
+#[derive(Clone, Debug, Default)]
+pub struct CacheStats {
+    /// Number of strings in the cache.
+    pub entries: usize,
+    /// Total length of all strings, not counting headers or null terminators.
+    pub string_bytes: usize,
+    /// Bytes handed out by the string allocators, including headers, null
+    /// terminators and padding.
+    pub allocated_bytes: usize,
+    /// Bytes reserved by the string allocators.
+    pub capacity_bytes: usize,
+    /// Bytes left unused at the end of allocators that were retired because
+    /// the next string did not fit.
+    pub wasted_bytes: usize,
+    /// Number of slots in the hash tables.
+    pub table_capacity: usize,
+    /// The longest probe sequence needed to find any string.
+    pub max_probe: usize,
+    /// Statistics for each bin. Bins that have never been used are all zero.
+    pub bins: vec::Vec<BinStats>,
+}
+
+/// Memory usage of a single bin (shard) of the string cache.
+#[derive(Clone, Debug, Default)]
+pub struct BinStats {
+    /// Number of strings in the bin.
+    pub entries: usize,
+    /// Total length of all strings, not counting headers or null terminators.
+    pub string_bytes: usize,
+    /// Bytes handed out by the string allocators.
+    pub allocated_bytes: usize,
+    /// Bytes reserved by the string allocators.
+    pub capacity_bytes: usize,
+    /// Bytes left unused at the end of retired allocators.
+    pub wasted_bytes: usize,
+    /// Number of slots in the hash table.
+    pub table_capacity: usize,
+    /// The longest probe sequence needed to find any string in the bin.
+    pub max_probe: usize,
+}
+
+impl CacheStats {
+    pub(crate) fn from_bins(bins: vec::Vec<BinStats>) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for bin in bins.iter() {
+            stats.entries += bin.entries;
+            stats.string_bytes += bin.string_bytes;
+            stats.allocated_bytes += bin.allocated_bytes;
+            stats.capacity_bytes += bin.capacity_bytes;
+            stats.wasted_bytes += bin.wasted_bytes;
+            stats.table_capacity += bin.table_capacity;
+            stats.max_probe = stats.max_probe.max(bin.max_probe);
+        }
+        stats.bins = bins;
+        stats
+    }
+}
+
+/// An iterator over the strings in the global string cache.
+///
+/// Created by [`cache_iter`](crate::cache_iter).
+pub struct StringCacheIterator {
+    // Each chunk is the range from the next entry to visit up to the end of
+    // an allocator. Entries are packed back to back at `StringCacheEntry`
+    // alignment, so we can walk a chunk by skipping over each entry in turn.
+    chunks: vec::Vec<(*const u8, *const u8)>,
+}
+
+impl StringCacheIterator {
+    pub(crate) fn new(chunks: vec::Vec<(*const u8, *const u8)>) -> StringCacheIterator {
+        StringCacheIterator { chunks }
+    }
+}
+
+impl Iterator for StringCacheIterator {
+    type Item = Estr;
+
+    fn next(&mut self) -> Option<Estr> {
+        loop {
+            let (ptr, end) = self.chunks.last_mut()?;
+            if *ptr < *end {
+                // This is safe as long as the chunk was fully written when the
+                // snapshot was taken, which is guaranteed because we only
+                // record the range below the allocator's current pointer.
+                unsafe {
+                    let entry = ptr.cast::<StringCacheEntry>();
+                    let char_ptr = entry.add(1) as *const u8;
+                    let size = mem::size_of::<StringCacheEntry>() + (*entry).len + 1;
+                    *ptr = ptr.add(size.next_multiple_of(mem::align_of::<StringCacheEntry>()));
+                    return Some(Estr {
+                        char_ptr: ptr::NonNull::new_unchecked(char_ptr as *mut _),
+                    });
+                }
+            }
+            self.chunks.pop();
+        }
+    }
+}
+
+// The strings we point at are immutable and never freed.
+unsafe impl Send for StringCacheIterator {}
+unsafe impl Sync for StringCacheIterator {}